@@ -0,0 +1,302 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use flagsmith::flagsmith::models::SDKTrait;
+use flagsmith::flagsmith::offline_handler::InMemoryHandler;
+use flagsmith::{Flagsmith, FlagsmithOptions};
+use flagsmith_flag_engine::environments::Environment;
+use flagsmith_flag_engine::identities::Trait;
+use flagsmith_flag_engine::types::{FlagsmithValue, FlagsmithValueType};
+use serde_json::json;
+use std::hint::black_box;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ENVIRONMENT_KEY: &str = "ser.benchmark_environment_key";
+const IDENTIFIER: &str = "benchmark_identity";
+// Identifiers `overridden_0`..`overridden_N` carry identity overrides
+const OVERRIDDEN_IDENTIFIER: &str = "overridden_0";
+const THREAD_COUNTS: [usize; 3] = [2, 4, 8];
+
+struct DocumentSize {
+    name: &'static str,
+    features: usize,
+    segments: usize,
+    rules_per_segment: usize,
+    overrides_per_segment: usize,
+    identity_overrides: usize,
+    multivariate_options: usize,
+}
+
+const DOCUMENT_SIZES: [DocumentSize; 3] = [
+    DocumentSize {
+        name: "small",
+        features: 10,
+        segments: 5,
+        rules_per_segment: 1,
+        overrides_per_segment: 1,
+        identity_overrides: 10,
+        multivariate_options: 2,
+    },
+    DocumentSize {
+        name: "medium",
+        features: 100,
+        segments: 25,
+        rules_per_segment: 3,
+        overrides_per_segment: 2,
+        identity_overrides: 500,
+        multivariate_options: 3,
+    },
+    DocumentSize {
+        name: "large",
+        features: 500,
+        segments: 100,
+        rules_per_segment: 5,
+        overrides_per_segment: 4,
+        identity_overrides: 2_000,
+        multivariate_options: 4,
+    },
+];
+
+fn feature_state_json(feature_id: usize, value: &str, mv_options: usize) -> serde_json::Value {
+    let multivariate_values: Vec<serde_json::Value> = (0..mv_options)
+        .map(|option| {
+            json!({
+                "id": feature_id * 100 + option,
+                "percentage_allocation": 100.0 / (mv_options as f64 + 1.0),
+                "multivariate_feature_option": {
+                    "id": feature_id * 100 + option,
+                    "value": format!("{}_variant_{}", value, option)
+                }
+            })
+        })
+        .collect();
+    json!({
+        "django_id": feature_id,
+        "featurestate_uuid": format!("00000000-0000-0000-0000-{:012}", feature_id),
+        "feature": {
+            "id": feature_id,
+            "name": format!("feature_{}", feature_id),
+            "type": if mv_options > 0 { "MULTIVARIATE" } else { "STANDARD" }
+        },
+        "feature_state_value": value,
+        "enabled": feature_id % 2 == 0,
+        "multivariate_feature_state_values": multivariate_values
+    })
+}
+
+// Builds a synthetic document following the shape of `tests/fixtures/environment.json`
+fn environment_document(size: &DocumentSize) -> Environment {
+    let mut document: serde_json::Value =
+        serde_json::from_str(include_str!("../tests/fixtures/environment.json")).unwrap();
+
+    let feature_states: Vec<serde_json::Value> = (1..=size.features)
+        .map(|feature_id| {
+            // Every fourth feature is multivariate
+            let mv_options = match feature_id % 4 {
+                0 => size.multivariate_options,
+                _ => 0,
+            };
+            feature_state_json(feature_id, &format!("value_{}", feature_id), mv_options)
+        })
+        .collect();
+
+    let segments: Vec<serde_json::Value> = (1..=size.segments)
+        .map(|segment_id| {
+            let rules: Vec<serde_json::Value> = (0..size.rules_per_segment)
+                .map(|rule| {
+                    json!({
+                        "type": if rule % 2 == 0 { "ALL" } else { "ANY" },
+                        "rules": [],
+                        "conditions": [
+                            {
+                                "operator": "EQUAL",
+                                "property_": format!("trait_{}", rule),
+                                "value": format!("value_{}", rule)
+                            },
+                            {
+                                "operator": "GREATER_THAN",
+                                "property_": "age",
+                                "value": (segment_id % 60).to_string()
+                            }
+                        ]
+                    })
+                })
+                .collect();
+            let segment_overrides: Vec<serde_json::Value> = (0..size.overrides_per_segment)
+                .map(|i| {
+                    let feature_id = (segment_id * 7 + i) % size.features + 1;
+                    let mut feature_state =
+                        feature_state_json(feature_id, &format!("segment_{}_value", segment_id), 0);
+                    feature_state["django_id"] = json!(size.features * segment_id + i + 1);
+                    feature_state["feature_segment"] = json!({ "priority": segment_id });
+                    feature_state
+                })
+                .collect();
+            json!({
+                "id": segment_id,
+                "name": format!("segment_{}", segment_id),
+                "rules": [{ "type": "ALL", "conditions": [], "rules": rules }],
+                "feature_states": segment_overrides
+            })
+        })
+        .collect();
+
+    let identity_overrides: Vec<serde_json::Value> = (0..size.identity_overrides)
+        .map(|i| {
+            // Overrides are shared between identities, as they typically are
+            let feature_id = i % 10 % size.features + 1;
+            json!({
+                "identifier": format!("overridden_{}", i),
+                "environment_api_key": document["api_key"],
+                "created_date": "2019-08-27T14:53:45.698555Z",
+                "identity_features": [
+                    feature_state_json(feature_id, &format!("overridden_{}", i % 10), 0)
+                ]
+            })
+        })
+        .collect();
+
+    document["feature_states"] = json!(feature_states);
+    document["project"]["segments"] = json!(segments);
+    document["identity_overrides"] = json!(identity_overrides);
+    serde_json::from_value(document).unwrap()
+}
+
+fn local_flagsmith(size: &DocumentSize) -> Flagsmith {
+    let flagsmith_options = FlagsmithOptions {
+        offline_handler: Some(Box::new(InMemoryHandler::new(environment_document(size)))),
+        ..Default::default()
+    };
+    Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options)
+}
+
+// Traits matching roughly half of the generated segment rules
+fn identity_traits() -> Vec<Trait> {
+    let mut traits: Vec<Trait> = (0..5)
+        .map(|rule| Trait {
+            trait_key: format!("trait_{}", rule),
+            trait_value: FlagsmithValue {
+                value: format!("value_{}", rule),
+                value_type: FlagsmithValueType::String,
+            },
+        })
+        .collect();
+    traits.push(Trait {
+        trait_key: "age".to_string(),
+        trait_value: FlagsmithValue {
+            value: "30".to_string(),
+            value_type: FlagsmithValueType::Integer,
+        },
+    });
+    traits
+}
+
+fn sdk_traits() -> Vec<SDKTrait> {
+    identity_traits()
+        .into_iter()
+        .map(|t| SDKTrait::new(t.trait_key, t.trait_value))
+        .collect()
+}
+
+fn configure_group(group: &mut criterion::BenchmarkGroup<criterion::measurement::WallTime>) {
+    group.sample_size(20);
+    group.measurement_time(Duration::from_secs(3));
+}
+
+fn single_thread(c: &mut Criterion) {
+    for size in DOCUMENT_SIZES.iter() {
+        let flagsmith = local_flagsmith(size);
+        let mut group = c.benchmark_group(format!("single_thread/{}", size.name));
+        configure_group(&mut group);
+
+        group.bench_function("get_environment_flags", |b| {
+            b.iter(|| black_box(flagsmith.get_environment_flags().unwrap()))
+        });
+        group.bench_function("get_identity_flags", |b| {
+            b.iter(|| {
+                black_box(
+                    flagsmith
+                        .get_identity_flags(IDENTIFIER, Some(sdk_traits()), None)
+                        .unwrap(),
+                )
+            })
+        });
+        group.bench_function("get_identity_flags/identity_override", |b| {
+            b.iter(|| {
+                black_box(
+                    flagsmith
+                        .get_identity_flags(OVERRIDDEN_IDENTIFIER, Some(sdk_traits()), None)
+                        .unwrap(),
+                )
+            })
+        });
+        group.bench_function("get_identity_segments", |b| {
+            b.iter(|| {
+                black_box(
+                    flagsmith
+                        .get_identity_segments(IDENTIFIER, Some(identity_traits()))
+                        .unwrap(),
+                )
+            })
+        });
+        group.finish();
+    }
+}
+
+// Each iteration runs one evaluation per thread, all sharing the same client
+fn contended<F>(c: &mut Criterion, name: &str, evaluate: F)
+where
+    F: Fn(&Flagsmith) + Sync,
+{
+    for size in DOCUMENT_SIZES.iter() {
+        let flagsmith = local_flagsmith(size);
+        let mut group = c.benchmark_group(format!("contended/{}/{}", size.name, name));
+        configure_group(&mut group);
+
+        for threads in THREAD_COUNTS {
+            group.throughput(Throughput::Elements(threads as u64));
+            group.bench_with_input(
+                BenchmarkId::from_parameter(threads),
+                &threads,
+                |b, &threads| {
+                    b.iter_custom(|iters| {
+                        let start = Instant::now();
+                        thread::scope(|scope| {
+                            for _ in 0..threads {
+                                scope.spawn(|| {
+                                    for _ in 0..iters {
+                                        evaluate(&flagsmith);
+                                    }
+                                });
+                            }
+                        });
+                        start.elapsed()
+                    })
+                },
+            );
+        }
+        group.finish();
+    }
+}
+
+fn multi_thread(c: &mut Criterion) {
+    contended(c, "get_environment_flags", |flagsmith| {
+        black_box(flagsmith.get_environment_flags().unwrap());
+    });
+    contended(c, "get_identity_flags", |flagsmith| {
+        black_box(
+            flagsmith
+                .get_identity_flags(IDENTIFIER, Some(sdk_traits()), None)
+                .unwrap(),
+        );
+    });
+    contended(c, "get_identity_segments", |flagsmith| {
+        black_box(
+            flagsmith
+                .get_identity_segments(IDENTIFIER, Some(identity_traits()))
+                .unwrap(),
+        );
+    });
+}
+
+criterion_group!(benches, single_thread, multi_thread);
+criterion_main!(benches);
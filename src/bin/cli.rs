@@ -0,0 +1,321 @@
+// Evaluates flags against environment documents, e.g. to debug the flags of an
+// identity without writing a program:
+//
+//   flagsmith-cli fetch --environment-key ser.xxx --output environment.json
+//   flagsmith-cli identity-flags --document environment.json --identifier user_1 --trait plan=premium
+//   flagsmith-cli diff --base staging.json --other-key ser.yyy
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use flagsmith::flagsmith::diff::diff_environments;
+use flagsmith::flagsmith::models::{Flags, SDKTrait};
+use flagsmith::flagsmith::offline_handler::{LocalFileHandler, OfflineHandler};
+use flagsmith::{Flagsmith, FlagsmithOptions, IdentityContext};
+use flagsmith_flag_engine::environments::Environment;
+use flagsmith_flag_engine::segments::Segment;
+use flagsmith_flag_engine::types::FlagsmithValue;
+use serde_json::json;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "flagsmith-cli", version, about = "Evaluates Flagsmith flags")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetches an environment document from the Flagsmith API and saves it to a file
+    Fetch {
+        /// Server-side environment key
+        #[arg(long, env = "FLAGSMITH_ENVIRONMENT_KEY")]
+        environment_key: String,
+        #[command(flatten)]
+        api: ApiArgs,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Evaluates the environment flags
+    EnvironmentFlags {
+        #[command(flatten)]
+        document: DocumentArgs,
+    },
+    /// Evaluates the flags of an identity
+    IdentityFlags {
+        #[command(flatten)]
+        document: DocumentArgs,
+        #[command(flatten)]
+        identity: IdentityArgs,
+    },
+    /// Lists the segments an identity belongs to
+    Segments {
+        #[command(flatten)]
+        document: DocumentArgs,
+        #[command(flatten)]
+        identity: IdentityArgs,
+    },
+    /// Compares two environment documents, each read from a file or fetched from
+    /// the Flagsmith API with a server-side environment key
+    Diff {
+        #[arg(
+            long,
+            required_unless_present = "base_key",
+            conflicts_with = "base_key"
+        )]
+        base: Option<PathBuf>,
+        #[arg(long)]
+        base_key: Option<String>,
+        #[arg(
+            long,
+            required_unless_present = "other_key",
+            conflicts_with = "other_key"
+        )]
+        other: Option<PathBuf>,
+        #[arg(long)]
+        other_key: Option<String>,
+        #[command(flatten)]
+        api: ApiArgs,
+        /// `table` lists one difference per line
+        #[arg(long, value_enum, default_value_t = Format::Table)]
+        format: Format,
+    },
+}
+
+#[derive(Args)]
+struct ApiArgs {
+    #[arg(long, env = "FLAGSMITH_API_URL")]
+    api_url: Option<String>,
+    /// Seconds to wait for environment documents
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+}
+
+#[derive(Args)]
+struct DocumentArgs {
+    /// Environment document, e.g. saved with `fetch`
+    #[arg(long, short)]
+    document: PathBuf,
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+}
+
+#[derive(Args)]
+struct IdentityArgs {
+    #[arg(long, short)]
+    identifier: String,
+    /// Trait of the identity, repeatable. JSON scalars such as `42` or `true` are
+    /// parsed as such, anything else is a string.
+    #[arg(long = "trait", value_name = "KEY=VALUE", value_parser = parse_trait)]
+    traits: Vec<(String, FlagsmithValue)>,
+    /// Traits of the identity as a JSON object, e.g. `{"plan": "premium", "age": 42}`
+    #[arg(long, value_name = "JSON")]
+    traits_json: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+}
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Command::Fetch {
+            environment_key,
+            api,
+            output,
+        } => {
+            // Saved as served, as the engine model drops some of its fields
+            let environment_document = fetch_client(environment_key, &api)?
+                .get_environment_document_json()
+                .ok_or("environment document not loaded")?;
+            std::fs::write(
+                &output,
+                serde_json::to_string_pretty(&*environment_document)?,
+            )?;
+            eprintln!("Saved environment document to {}", output.display());
+            Ok(())
+        }
+        Command::EnvironmentFlags { document } => {
+            let flags = load(&document)?.get_environment_flags()?;
+            print_flags(&flags, document.format);
+            Ok(())
+        }
+        Command::IdentityFlags { document, identity } => {
+            let flags = load(&document)?.get_identity_flags_for(identity.context()?)?;
+            print_flags(&flags, document.format);
+            Ok(())
+        }
+        Command::Segments { document, identity } => {
+            let segments = load(&document)?.get_identity_segments_for(identity.context()?)?;
+            print_segments(&segments, document.format);
+            Ok(())
+        }
+        Command::Diff {
+            base,
+            base_key,
+            other,
+            other_key,
+            api,
+            format,
+        } => {
+            let base = match base {
+                Some(path) => load_environment(&path)?,
+                None => fetch(base_key.unwrap(), &api)?,
+            };
+            let other = match other {
+                Some(path) => load_environment(&path)?,
+                None => fetch(other_key.unwrap(), &api)?,
+            };
+            let diff = diff_environments(&base, &other);
+            match format {
+                Format::Table if diff.is_empty() => println!("No differences."),
+                Format::Table => print!("{}", diff),
+                Format::Json => println!("{}", serde_json::to_string_pretty(&diff.to_json())?),
+            }
+            Ok(())
+        }
+    }
+}
+
+fn fetch(environment_key: String, api: &ApiArgs) -> Result<Environment, Box<dyn Error>> {
+    Ok(fetch_client(environment_key, api)?
+        .get_environment_document()
+        .ok_or("environment document not loaded")?)
+}
+
+// Client in local evaluation mode, once it has loaded the environment document
+fn fetch_client(environment_key: String, api: &ApiArgs) -> Result<Flagsmith, Box<dyn Error>> {
+    if !environment_key.starts_with("ser.") {
+        return Err("fetching an environment document requires a server-side environment key (starts with 'ser.')".into());
+    }
+    let mut flagsmith_options = FlagsmithOptions {
+        enable_local_evaluation: true,
+        ..Default::default()
+    };
+    if let Some(api_url) = &api.api_url {
+        flagsmith_options.api_url = api_url.clone();
+    }
+    let flagsmith = Flagsmith::new(environment_key, flagsmith_options);
+    flagsmith.wait_for_environment(Duration::from_secs(api.timeout))?;
+    Ok(flagsmith)
+}
+
+fn load_handler(path: &Path) -> Result<LocalFileHandler, Box<dyn Error>> {
+    let path = path.to_string_lossy();
+    Ok(LocalFileHandler::new(&path)
+        .map_err(|e| format!("failed to load environment document {}: {}", path, e))?)
+}
+
+fn load_environment(path: &Path) -> Result<Environment, Box<dyn Error>> {
+    Ok(load_handler(path)?.get_environment())
+}
+
+fn load(document: &DocumentArgs) -> Result<Flagsmith, Box<dyn Error>> {
+    let handler = load_handler(&document.document)?;
+    let flagsmith_options = FlagsmithOptions {
+        offline_handler: Some(Box::new(handler)),
+        offline_mode: true,
+        ..Default::default()
+    };
+    Ok(Flagsmith::new(String::new(), flagsmith_options))
+}
+
+impl IdentityArgs {
+    // Identity with the traits given with `--traits-json`, then the ones given with `--trait`
+    fn context(&self) -> Result<IdentityContext, Box<dyn Error>> {
+        let mut traits = vec![];
+        if let Some(traits_json) = &self.traits_json {
+            let object: serde_json::Map<String, serde_json::Value> =
+                serde_json::from_str(traits_json)
+                    .map_err(|e| format!("--traits-json must be a JSON object: {}", e))?;
+            traits = SDKTrait::from_json(&object)?;
+        }
+        Ok(IdentityContext::new(self.identifier.as_str())
+            .with_traits(traits)
+            .with_traits(self.traits.iter().cloned()))
+    }
+}
+
+fn parse_trait(raw: &str) -> Result<(String, FlagsmithValue), String> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{}`", raw))?;
+    let value = match serde_json::from_str::<serde_json::Value>(value) {
+        Ok(scalar @ (serde_json::Value::Bool(_) | serde_json::Value::Number(_))) => {
+            serde_json::from_value(scalar).map_err(|e| e.to_string())?
+        }
+        _ => serde_json::from_value(json!(value)).map_err(|e| e.to_string())?,
+    };
+    Ok((key.to_string(), value))
+}
+
+fn print_flags(flags: &Flags, format: Format) {
+    let mut flags = flags.all_flags();
+    flags.sort_by(|a, b| a.feature_name.cmp(&b.feature_name));
+    match format {
+        Format::Table => print_table(
+            &["FEATURE", "ENABLED", "VALUE"],
+            flags
+                .iter()
+                .map(|flag| {
+                    vec![
+                        flag.feature_name.clone(),
+                        flag.enabled.to_string(),
+                        flag.value.value.clone(),
+                    ]
+                })
+                .collect(),
+        ),
+        Format::Json => {
+            let flags: Vec<_> = flags.iter().map(|flag| flag.to_api_flag()).collect();
+            println!("{}", serde_json::to_string_pretty(&flags).unwrap());
+        }
+    }
+}
+
+fn print_segments(segments: &[Segment], format: Format) {
+    match format {
+        Format::Table => print_table(
+            &["ID", "NAME"],
+            segments
+                .iter()
+                .map(|segment| vec![segment.id.to_string(), segment.name.clone()])
+                .collect(),
+        ),
+        Format::Json => {
+            let segments: Vec<_> = segments
+                .iter()
+                .map(|segment| json!({"id": segment.id, "name": segment.name}))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&segments).unwrap());
+        }
+    }
+}
+
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let headers = headers.iter().map(|header| header.to_string()).collect();
+    for row in std::iter::once(headers).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
@@ -0,0 +1,61 @@
+// Serves the Flagsmith flags API from a locally evaluated environment.
+//
+// Configured through environment variables:
+// - `FLAGSMITH_ENVIRONMENT_KEY`: server-side environment key, to fetch and refresh
+//   the environment document from the Flagsmith API
+// - `FLAGSMITH_ENVIRONMENT_FILE`: environment document to serve instead, without
+//   calling the Flagsmith API
+// - `FLAGSMITH_API_URL`: Flagsmith API URL, defaults to the Flagsmith edge API
+// - `FLAGSMITH_RELAY_ADDRESS`: address to listen on, defaults to `127.0.0.1:8000`
+// - `FLAGSMITH_RELAY_KEY`: key that requests for the environment document must send
+//   as `X-Environment-Key`, defaults to `FLAGSMITH_ENVIRONMENT_KEY` if server-side.
+//   Without either, the environment document is not served.
+use flagsmith::flagsmith::offline_handler::LocalFileHandler;
+use flagsmith::flagsmith::relay::Relay;
+use flagsmith::{Flagsmith, FlagsmithOptions};
+use std::env;
+use std::process;
+
+const DEFAULT_RELAY_ADDRESS: &str = "127.0.0.1:8000";
+
+fn main() {
+    let mut flagsmith_options = FlagsmithOptions::default();
+    if let Ok(api_url) = env::var("FLAGSMITH_API_URL") {
+        flagsmith_options.api_url = api_url;
+    }
+
+    let environment_key = match env::var("FLAGSMITH_ENVIRONMENT_FILE") {
+        Ok(path) => {
+            let handler = LocalFileHandler::new(&path).unwrap_or_else(|e| {
+                eprintln!("Failed to load environment document {}: {}", path, e);
+                process::exit(1);
+            });
+            flagsmith_options.offline_handler = Some(Box::new(handler));
+            flagsmith_options.offline_mode = true;
+            env::var("FLAGSMITH_ENVIRONMENT_KEY").unwrap_or_default()
+        }
+        Err(_) => {
+            let environment_key = env::var("FLAGSMITH_ENVIRONMENT_KEY").unwrap_or_else(|_| {
+                eprintln!("FLAGSMITH_ENVIRONMENT_KEY or FLAGSMITH_ENVIRONMENT_FILE must be set");
+                process::exit(1);
+            });
+            flagsmith_options.enable_local_evaluation = true;
+            // Answer with an error rather than relay requests to the Flagsmith API
+            // while the environment has not been loaded
+            flagsmith_options.strict_local_evaluation = true;
+            environment_key
+        }
+    };
+
+    let address =
+        env::var("FLAGSMITH_RELAY_ADDRESS").unwrap_or_else(|_| DEFAULT_RELAY_ADDRESS.to_string());
+    let mut relay = Relay::new(Flagsmith::new(environment_key, flagsmith_options));
+    if let Ok(relay_key) = env::var("FLAGSMITH_RELAY_KEY") {
+        relay = relay.with_document_key(relay_key);
+    }
+    eprintln!("Serving Flagsmith flags on {}", address);
+    if let Err(e) = relay.serve(&address) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -37,6 +37,7 @@ impl From<url::ParseError> for Error {
     }
 }
 
+#[cfg(feature = "reqwest")]
 impl From<reqwest::Error> for Error {
     fn from(e: reqwest::Error) -> Self {
         Error::new(ErrorKind::FlagsmithAPIError, e.to_string())
@@ -1,10 +1,14 @@
 use flume;
 use log::{debug, warn};
-use reqwest::header::HeaderMap;
 use serde_json;
 use std::{collections::HashMap, thread};
 
 use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use super::request_options::RequestOptions;
+use super::{telemetry, ApiClient};
+
 static ANALYTICS_TIMER_IN_MILLI: u64 = 10 * 1000;
 
 #[derive(Clone, Debug)]
@@ -14,18 +18,24 @@ pub struct AnalyticsProcessor {
 }
 
 impl AnalyticsProcessor {
+    #[cfg(feature = "reqwest")]
     pub fn new(
         api_url: String,
-        headers: HeaderMap,
+        headers: http::HeaderMap,
         timeout: std::time::Duration,
         timer: Option<u64>,
     ) -> Self {
+        let client = ApiClient {
+            transport: Arc::new(super::transport::ReqwestTransport::default()),
+            headers,
+            timeout,
+        };
+        AnalyticsProcessor::with_client(client, api_url, timer)
+    }
+
+    // Processor flushing through the client of the environment
+    pub(super) fn with_client(client: ApiClient, api_url: String, timer: Option<u64>) -> Self {
         let (tx, rx) = flume::unbounded();
-        let client = reqwest::blocking::Client::builder()
-            .default_headers(headers)
-            .timeout(timeout)
-            .build()
-            .unwrap();
         let analytics_endpoint = format!("{}analytics/flags/", api_url);
         let timer = timer.unwrap_or(ANALYTICS_TIMER_IN_MILLI);
 
@@ -68,31 +78,47 @@ impl AnalyticsProcessor {
             _analytics_data: Arc::clone(&analytics_data_arc),
         };
     }
+
     pub fn track_feature(&self, feature_name: &str) {
         self.tx.send(feature_name.to_string()).unwrap();
     }
+
+    // Number of evaluations not yet flushed: those waiting in the channel and
+    // those already counted by the analytics thread
+    pub(crate) fn queue_depth(&self) -> usize {
+        let pending: u32 = self._analytics_data.read().unwrap().values().sum();
+        self.tx.len() + pending as usize
+    }
 }
 
-fn flush(
-    client: &reqwest::blocking::Client,
-    analytics_data: &HashMap<String, u32>,
-    analytics_endpoint: &str,
-) {
+fn flush(client: &ApiClient, analytics_data: &HashMap<String, u32>, analytics_endpoint: &str) {
     if analytics_data.len() == 0 {
         return;
     }
+    let span = telemetry::analytics_flush_span(analytics_data.len());
+    let started = Instant::now();
     let body = serde_json::to_string(&analytics_data).unwrap();
-    let resp = client.post(analytics_endpoint).body(body).send();
+    let resp = client.send(
+        http::Method::POST,
+        analytics_endpoint.to_string(),
+        Some(body.into_bytes()),
+        &RequestOptions::default(),
+    );
+    let status_code = resp.as_ref().ok().map(|resp| resp.status);
+    span.record_http(status_code, started.elapsed());
+    telemetry::record_http_request("analytics", status_code, started.elapsed());
+    telemetry::record_analytics_flush(status_code.is_some_and(|status_code| status_code < 400));
     if resp.is_err() {
         warn!("Failed to send analytics data");
     }
 }
 
-#[cfg(test)]
+// The tests flush through `AnalyticsProcessor::new`, which sends with reqwest
+#[cfg(all(test, feature = "reqwest"))]
 mod tests {
     use super::*;
+    use http::header;
     use httpmock::prelude::*;
-    use reqwest::header;
 
     #[test]
     fn track_feature_updates_analytics_data() {
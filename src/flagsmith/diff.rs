@@ -0,0 +1,283 @@
+use flagsmith_flag_engine::environments::Environment;
+use flagsmith_flag_engine::features::FeatureState;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifferenceKind {
+    Feature,
+    Segment,
+    SegmentOverride,
+    IdentityOverride,
+}
+
+// A difference between two environments. `field` is `None` for items present in
+// only one of them, in which case either `base` or `other` is `None`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Difference {
+    pub kind: DifferenceKind,
+    // Feature or segment name; `segment/feature` and `identifier/feature` for overrides
+    pub name: String,
+    pub field: Option<String>,
+    pub base: Option<serde_json::Value>,
+    pub other: Option<serde_json::Value>,
+}
+
+// Differences between two environments, e.g. staging and production before
+// promoting changes. Displays as one line per difference:
+// `+` for added items, `-` for removed ones and `~` for changed fields.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct EnvironmentDiff {
+    pub differences: Vec<Difference>,
+}
+
+impl EnvironmentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!(self.differences)
+    }
+}
+
+impl fmt::Display for EnvironmentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for difference in &self.differences {
+            let kind = json!(difference.kind);
+            let kind = kind.as_str().unwrap().replace('_', " ");
+            match (&difference.field, &difference.base, &difference.other) {
+                (Some(field), Some(base), Some(other)) => writeln!(
+                    f,
+                    "~ {} {} {}: {} -> {}",
+                    kind, difference.name, field, base, other
+                )?,
+                (_, None, Some(other)) => writeln!(f, "+ {} {} {}", kind, difference.name, other)?,
+                (_, Some(base), None) => writeln!(f, "- {} {} {}", kind, difference.name, base)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+type Fields = BTreeMap<&'static str, serde_json::Value>;
+
+// Compares features, by name, with their enabled state, value and multivariate
+// options, then segments with their rules, segment overrides and identity overrides
+pub fn diff_environments(base: &Environment, other: &Environment) -> EnvironmentDiff {
+    let mut differences = vec![];
+    diff_items(
+        DifferenceKind::Feature,
+        features(base),
+        features(other),
+        &mut differences,
+    );
+    diff_items(
+        DifferenceKind::Segment,
+        segments(base),
+        segments(other),
+        &mut differences,
+    );
+    diff_items(
+        DifferenceKind::SegmentOverride,
+        segment_overrides(base),
+        segment_overrides(other),
+        &mut differences,
+    );
+    diff_items(
+        DifferenceKind::IdentityOverride,
+        identity_overrides(base),
+        identity_overrides(other),
+        &mut differences,
+    );
+    EnvironmentDiff { differences }
+}
+
+fn diff_items(
+    kind: DifferenceKind,
+    base: BTreeMap<String, Fields>,
+    other: BTreeMap<String, Fields>,
+    differences: &mut Vec<Difference>,
+) {
+    let names: BTreeSet<&String> = base.keys().chain(other.keys()).collect();
+    for name in names {
+        match (base.get(name), other.get(name)) {
+            (Some(base_fields), Some(other_fields)) => {
+                for (field, base_value) in base_fields {
+                    let other_value = &other_fields[field];
+                    if base_value != other_value {
+                        differences.push(Difference {
+                            kind,
+                            name: name.clone(),
+                            field: Some(field.to_string()),
+                            base: Some(base_value.clone()),
+                            other: Some(other_value.clone()),
+                        });
+                    }
+                }
+            }
+            (base_fields, other_fields) => differences.push(Difference {
+                kind,
+                name: name.clone(),
+                field: None,
+                base: base_fields.map(|fields| json!(fields)),
+                other: other_fields.map(|fields| json!(fields)),
+            }),
+        }
+    }
+}
+
+fn feature_state_fields(feature_state: &FeatureState) -> Fields {
+    BTreeMap::from([
+        ("enabled", json!(feature_state.enabled)),
+        ("value", json!(feature_state.get_value(None))),
+    ])
+}
+
+fn features(environment: &Environment) -> BTreeMap<String, Fields> {
+    environment
+        .feature_states
+        .iter()
+        .map(|feature_state| {
+            let mut fields = feature_state_fields(feature_state);
+            let variants: Vec<_> = feature_state
+                .multivariate_feature_state_values
+                .iter()
+                .map(|variant| {
+                    json!({
+                        "value": variant.multivariate_feature_option.value,
+                        "percentage_allocation": variant.percentage_allocation,
+                    })
+                })
+                .collect();
+            fields.insert("variants", json!(variants));
+            (feature_state.feature.name.clone(), fields)
+        })
+        .collect()
+}
+
+fn segments(environment: &Environment) -> BTreeMap<String, Fields> {
+    environment
+        .project
+        .segments
+        .iter()
+        .map(|segment| {
+            let fields = BTreeMap::from([("rules", json!(segment.rules))]);
+            (segment.name.clone(), fields)
+        })
+        .collect()
+}
+
+fn segment_overrides(environment: &Environment) -> BTreeMap<String, Fields> {
+    let mut overrides = BTreeMap::new();
+    for segment in &environment.project.segments {
+        for feature_state in &segment.feature_states {
+            let mut fields = feature_state_fields(feature_state);
+            let priority = feature_state
+                .feature_segment
+                .as_ref()
+                .map(|feature_segment| feature_segment.priority);
+            fields.insert("priority", json!(priority));
+            overrides.insert(
+                format!("{}/{}", segment.name, feature_state.feature.name),
+                fields,
+            );
+        }
+    }
+    overrides
+}
+
+fn identity_overrides(environment: &Environment) -> BTreeMap<String, Fields> {
+    let mut overrides = BTreeMap::new();
+    for identity in &environment.identity_overrides {
+        for feature_state in &identity.identity_features {
+            overrides.insert(
+                format!("{}/{}", identity.identifier, feature_state.feature.name),
+                feature_state_fields(feature_state),
+            );
+        }
+    }
+    overrides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flagsmith::environment_builder::{
+        EnvironmentBuilder, FeatureBuilder, RuleBuilder, SegmentBuilder,
+    };
+    use flagsmith_flag_engine::segments::constants::EQUAL;
+
+    fn builder(plan: &str, banner_enabled: bool) -> EnvironmentBuilder {
+        EnvironmentBuilder::new("ser.environment_key")
+            .feature(FeatureBuilder::new("banner").enabled(banner_enabled))
+            .feature(FeatureBuilder::new("colour").value("red"))
+            .segment(
+                SegmentBuilder::new("premium")
+                    .rule(RuleBuilder::all().condition("plan", EQUAL, plan))
+                    .feature_override("colour", true, "gold", 1),
+            )
+    }
+
+    #[test]
+    fn diff_environments_is_empty_for_identical_environments() {
+        // Given
+        let environment = builder("premium", true).build().unwrap();
+
+        // When
+        let diff = diff_environments(&environment, &environment);
+
+        // Then
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "");
+    }
+
+    #[test]
+    fn diff_environments_reports_each_kind_of_difference() {
+        // Given
+        let base = builder("premium", true).build().unwrap();
+        let other = builder("gold", false)
+            .feature(FeatureBuilder::new("new_feature"))
+            .identity_override("some_identifier", "colour", true, "blue")
+            .build()
+            .unwrap();
+
+        // When
+        let diff = diff_environments(&base, &other);
+
+        // Then
+        let summary: Vec<_> = diff
+            .differences
+            .iter()
+            .map(|difference| {
+                (
+                    difference.kind,
+                    difference.name.as_str(),
+                    difference.field.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (DifferenceKind::Feature, "banner", Some("enabled")),
+                (DifferenceKind::Feature, "new_feature", None),
+                (DifferenceKind::Segment, "premium", Some("rules")),
+                (
+                    DifferenceKind::IdentityOverride,
+                    "some_identifier/colour",
+                    None
+                ),
+            ]
+        );
+        let lines: Vec<String> = diff.to_string().lines().map(String::from).collect();
+        assert_eq!(lines[0], "~ feature banner enabled: true -> false");
+        assert!(lines[1].starts_with("+ feature new_feature {"));
+        assert!(lines[3].starts_with("+ identity override some_identifier/colour {"));
+        assert_eq!(diff.to_json()[1]["base"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,407 @@
+use crate::error;
+use flagsmith_flag_engine::environments::Environment;
+use flagsmith_flag_engine::segments::constants::{ALL_RULE, ANY_RULE, NONE_RULE};
+use serde_json::json;
+
+// Builds environment documents, e.g. for `InMemoryHandler` or, once written to a
+// file with `to_json`, for `LocalFileHandler`.
+// # Example
+// ```
+// use flagsmith::flagsmith::environment_builder::{
+//     EnvironmentBuilder, FeatureBuilder, RuleBuilder, SegmentBuilder,
+// };
+// use flagsmith_flag_engine::segments::constants::EQUAL;
+// let environment = EnvironmentBuilder::new("ser.environment_key")
+//     .feature(FeatureBuilder::new("banner").enabled(true).value("default"))
+//     .feature(FeatureBuilder::new("colour").value("red").variant("blue", 50.0))
+//     .segment(
+//         SegmentBuilder::new("premium")
+//             .rule(RuleBuilder::all().condition("plan", EQUAL, "premium"))
+//             .feature_override("banner", true, "premium", 1),
+//     )
+//     .identity_override("some_identifier", "banner", false, "hidden")
+//     .build()
+//     .unwrap();
+// ```
+#[derive(Clone, Debug)]
+pub struct EnvironmentBuilder {
+    api_key: String,
+    name: String,
+    features: Vec<FeatureBuilder>,
+    segments: Vec<SegmentBuilder>,
+    identity_overrides: Vec<(String, String, bool, serde_json::Value)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FeatureBuilder {
+    name: String,
+    enabled: bool,
+    value: serde_json::Value,
+    // Multivariate options as (value, percentage allocation)
+    variants: Vec<(serde_json::Value, f64)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SegmentBuilder {
+    name: String,
+    rules: Vec<RuleBuilder>,
+    // Segment overrides as (feature name, enabled, value, priority)
+    overrides: Vec<(String, bool, serde_json::Value, u32)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RuleBuilder {
+    rule_type: &'static str,
+    conditions: Vec<(String, String, String)>,
+    rules: Vec<RuleBuilder>,
+}
+
+impl EnvironmentBuilder {
+    pub fn new(api_key: &str) -> Self {
+        EnvironmentBuilder {
+            api_key: api_key.to_string(),
+            name: "Environment".to_string(),
+            features: vec![],
+            segments: vec![],
+            identity_overrides: vec![],
+        }
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn feature(mut self, feature: FeatureBuilder) -> Self {
+        self.features.push(feature);
+        self
+    }
+
+    pub fn segment(mut self, segment: SegmentBuilder) -> Self {
+        self.segments.push(segment);
+        self
+    }
+
+    // Overrides a feature for a single identity. Several features can be overridden
+    // for the same identity.
+    pub fn identity_override(
+        mut self,
+        identifier: &str,
+        feature_name: &str,
+        enabled: bool,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.identity_overrides.push((
+            identifier.to_string(),
+            feature_name.to_string(),
+            enabled,
+            value.into(),
+        ));
+        self
+    }
+
+    // Returns the environment document as served by the environment document endpoint.
+    // Fails if an override refers to a feature that was not added.
+    pub fn to_json(&self) -> Result<serde_json::Value, error::Error> {
+        let mut feature_state_ids = 0..;
+
+        let mut feature_states = vec![];
+        for (index, feature) in self.features.iter().enumerate() {
+            let mut feature_state = feature_state_json(
+                feature_state_ids.next().unwrap(),
+                index + 1,
+                &feature.name,
+                feature.enabled,
+                &feature.value,
+            );
+            feature_state["feature"]["type"] = json!(match feature.variants.is_empty() {
+                true => "STANDARD",
+                false => "MULTIVARIATE",
+            });
+            feature_state["multivariate_feature_state_values"] = feature
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(option_index, (value, percentage_allocation))| {
+                    let option_id = (index + 1) * 1000 + option_index;
+                    json!({
+                        "id": option_id,
+                        "percentage_allocation": percentage_allocation,
+                        "multivariate_feature_option": {"id": option_id, "value": value}
+                    })
+                })
+                .collect();
+            feature_states.push(feature_state);
+        }
+
+        let mut segments = vec![];
+        for (index, segment) in self.segments.iter().enumerate() {
+            let mut segment_feature_states = vec![];
+            for (feature_name, enabled, value, priority) in &segment.overrides {
+                let mut feature_state = feature_state_json(
+                    feature_state_ids.next().unwrap(),
+                    self.feature_id(feature_name)?,
+                    feature_name,
+                    *enabled,
+                    value,
+                );
+                feature_state["feature_segment"] = json!({ "priority": priority });
+                segment_feature_states.push(feature_state);
+            }
+            segments.push(json!({
+                "id": index + 1,
+                "name": segment.name,
+                "rules": segment.rules.iter().map(RuleBuilder::to_json).collect::<Vec<_>>(),
+                "feature_states": segment_feature_states
+            }));
+        }
+
+        let mut identity_overrides: Vec<serde_json::Value> = vec![];
+        for (identifier, feature_name, enabled, value) in &self.identity_overrides {
+            let feature_state = feature_state_json(
+                feature_state_ids.next().unwrap(),
+                self.feature_id(feature_name)?,
+                feature_name,
+                *enabled,
+                value,
+            );
+            match identity_overrides
+                .iter_mut()
+                .find(|identity| identity["identifier"] == json!(identifier))
+            {
+                Some(identity) => identity["identity_features"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(feature_state),
+                None => identity_overrides.push(json!({
+                    "identifier": identifier,
+                    "environment_api_key": self.api_key,
+                    "created_date": "2019-08-27T14:53:45.698555Z",
+                    "identity_features": [feature_state]
+                })),
+            }
+        }
+
+        Ok(json!({
+            "id": 1,
+            "api_key": self.api_key,
+            "name": self.name,
+            "project": {
+                "id": 1,
+                "name": "Project",
+                "organisation": {
+                    "id": 1,
+                    "name": "Organisation",
+                    "feature_analytics": false,
+                    "persist_trait_data": true,
+                    "stop_serving_flags": false
+                },
+                "hide_disabled_flags": false,
+                "segments": segments
+            },
+            "feature_states": feature_states,
+            "identity_overrides": identity_overrides
+        }))
+    }
+
+    pub fn build(&self) -> Result<Environment, error::Error> {
+        Ok(serde_json::from_value(self.to_json()?)?)
+    }
+
+    fn feature_id(&self, feature_name: &str) -> Result<usize, error::Error> {
+        self.features
+            .iter()
+            .position(|feature| feature.name == feature_name)
+            .map(|index| index + 1)
+            .ok_or(error::Error::new(
+                error::ErrorKind::FlagsmithClientError,
+                format!("Override of unknown feature {}", feature_name),
+            ))
+    }
+}
+
+impl FeatureBuilder {
+    // Disabled feature with no value
+    pub fn new(name: &str) -> Self {
+        FeatureBuilder {
+            name: name.to_string(),
+            enabled: false,
+            value: serde_json::Value::Null,
+            variants: vec![],
+        }
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    // Value served to identities falling outside of the multivariate options
+    pub fn value(mut self, value: impl Into<serde_json::Value>) -> Self {
+        self.value = value.into();
+        self
+    }
+
+    // Adds a multivariate option served to `percentage_allocation` percent of identities
+    pub fn variant(
+        mut self,
+        value: impl Into<serde_json::Value>,
+        percentage_allocation: f64,
+    ) -> Self {
+        self.variants.push((value.into(), percentage_allocation));
+        self
+    }
+}
+
+impl SegmentBuilder {
+    pub fn new(name: &str) -> Self {
+        SegmentBuilder {
+            name: name.to_string(),
+            rules: vec![],
+            overrides: vec![],
+        }
+    }
+
+    // Identities must match all of the segment rules
+    pub fn rule(mut self, rule: RuleBuilder) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    // Overrides a feature for the identities in the segment. When an identity is in
+    // several segments overriding the same feature, the lowest priority wins.
+    pub fn feature_override(
+        mut self,
+        feature_name: &str,
+        enabled: bool,
+        value: impl Into<serde_json::Value>,
+        priority: u32,
+    ) -> Self {
+        self.overrides
+            .push((feature_name.to_string(), enabled, value.into(), priority));
+        self
+    }
+}
+
+impl RuleBuilder {
+    pub fn all() -> Self {
+        RuleBuilder::new(ALL_RULE)
+    }
+
+    pub fn any() -> Self {
+        RuleBuilder::new(ANY_RULE)
+    }
+
+    pub fn none() -> Self {
+        RuleBuilder::new(NONE_RULE)
+    }
+
+    fn new(rule_type: &'static str) -> Self {
+        RuleBuilder {
+            rule_type,
+            conditions: vec![],
+            rules: vec![],
+        }
+    }
+
+    // Adds a condition on a trait, using one of the operators of
+    // `flagsmith_flag_engine::segments::constants`
+    pub fn condition(mut self, property: &str, operator: &str, value: &str) -> Self {
+        self.conditions.push((
+            property.to_string(),
+            operator.to_string(),
+            value.to_string(),
+        ));
+        self
+    }
+
+    pub fn rule(mut self, rule: RuleBuilder) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "type": self.rule_type,
+            "conditions": self
+                .conditions
+                .iter()
+                .map(|(property, operator, value)| {
+                    json!({"property_": property, "operator": operator, "value": value})
+                })
+                .collect::<Vec<_>>(),
+            "rules": self.rules.iter().map(RuleBuilder::to_json).collect::<Vec<_>>()
+        })
+    }
+}
+
+fn feature_state_json(
+    feature_state_id: usize,
+    feature_id: usize,
+    feature_name: &str,
+    enabled: bool,
+    value: &serde_json::Value,
+) -> serde_json::Value {
+    json!({
+        "django_id": feature_state_id + 1,
+        "featurestate_uuid": format!("00000000-0000-0000-0000-{:012}", feature_state_id + 1),
+        "feature": {"id": feature_id, "name": feature_name, "type": "STANDARD"},
+        "feature_state_value": value,
+        "enabled": enabled,
+        "multivariate_feature_state_values": []
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flagsmith_flag_engine::segments::constants::EQUAL;
+
+    #[test]
+    fn build_produces_environment_with_overrides() {
+        // Given
+        let builder = EnvironmentBuilder::new("ser.environment_key")
+            .feature(FeatureBuilder::new("feature_1").enabled(true).value("a"))
+            .feature(FeatureBuilder::new("feature_2").value(1).variant(2, 50.0))
+            .segment(
+                SegmentBuilder::new("segment")
+                    .rule(RuleBuilder::all().condition("plan", EQUAL, "premium"))
+                    .feature_override("feature_2", true, 3, 1),
+            )
+            .identity_override("identifier", "feature_1", false, "b")
+            .identity_override("identifier", "feature_2", false, "c");
+
+        // When
+        let environment = builder.build().unwrap();
+
+        // Then
+        assert_eq!(environment.api_key, "ser.environment_key");
+        assert_eq!(environment.feature_states.len(), 2);
+        assert_eq!(environment.project.segments.len(), 1);
+        let segment = &environment.project.segments[0];
+        assert_eq!(
+            segment.rules[0].conditions[0].property.as_deref(),
+            Some("plan")
+        );
+        assert_eq!(segment.feature_states[0].feature.id, 2);
+        assert_eq!(environment.identity_overrides.len(), 1);
+        assert_eq!(environment.identity_overrides[0].identity_features.len(), 2);
+    }
+
+    #[test]
+    fn build_fails_for_overrides_of_unknown_features() {
+        // Given
+        let builder = EnvironmentBuilder::new("ser.environment_key").identity_override(
+            "identifier",
+            "unknown",
+            true,
+            "value",
+        );
+
+        // When
+        let err = builder.build().err().unwrap();
+
+        // Then
+        assert_eq!(err.kind, error::ErrorKind::FlagsmithClientError);
+    }
+}
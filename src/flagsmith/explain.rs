@@ -0,0 +1,322 @@
+use flagsmith_flag_engine::engine::get_evaluation_result;
+use flagsmith_flag_engine::engine_eval::context::{
+    Condition, ConditionOperator, ConditionValue, EngineEvaluationContext, FeatureContext,
+    SegmentContext, SegmentMetadata, SegmentRule, SegmentRuleType,
+};
+use flagsmith_flag_engine::engine_eval::{is_context_in_segment, SegmentSource};
+use flagsmith_flag_engine::types::FlagsmithValue;
+use flagsmith_flag_engine::utils::hashing;
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Trace of a local evaluation for a single identity, as returned by
+// `Flagsmith::explain_identity`
+#[derive(Clone, Debug, Serialize)]
+pub struct IdentityExplanation {
+    pub identifier: String,
+    // Every segment in the environment, matched or not
+    pub segments: Vec<SegmentExplanation>,
+    pub flags: Vec<FlagExplanation>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SegmentExplanation {
+    pub name: String,
+    pub segment_id: Option<i32>,
+    pub source: SegmentSource,
+    pub matched: bool,
+    pub rules: Vec<RuleExplanation>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RuleExplanation {
+    pub rule_type: SegmentRuleType,
+    pub matched: bool,
+    pub conditions: Vec<ConditionExplanation>,
+    pub rules: Vec<RuleExplanation>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConditionExplanation {
+    pub operator: ConditionOperator,
+    pub property: String,
+    pub value: ConditionValue,
+    // Value the condition was evaluated against, `None` if the trait is not set
+    pub context_value: Option<FlagsmithValue>,
+    pub matched: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FlagExplanation {
+    pub feature_name: String,
+    pub enabled: bool,
+    pub value: FlagsmithValue,
+    // Reason reported by the engine, e.g. "DEFAULT" or "SPLIT; weight=50"
+    pub reason: String,
+    // Name of the segment whose override was chosen, if any
+    pub segment_override: Option<String>,
+    // Percentage (0-100) the identity hashed to for multivariate features
+    pub hash_percentage: Option<f32>,
+}
+
+// Explains the evaluation of `context`, which must hold an identity
+pub(crate) fn explain_evaluation(context: &EngineEvaluationContext) -> IdentityExplanation {
+    let result = get_evaluation_result(context);
+
+    // Mirror the engine: segments are processed in key order
+    let mut segment_keys: Vec<&String> = context.segments.keys().collect();
+    segment_keys.sort();
+
+    let mut segments = Vec::new();
+    let mut overrides: HashMap<&str, (&FeatureContext, &str)> = HashMap::new();
+    for segment_key in segment_keys {
+        let segment = &context.segments[segment_key];
+        let matched = is_context_in_segment(context, segment);
+        segments.push(SegmentExplanation {
+            name: segment.name.clone(),
+            segment_id: segment.metadata.segment_id,
+            source: segment.metadata.source.clone(),
+            matched,
+            rules: segment
+                .rules
+                .iter()
+                .map(|rule| explain_rule(context, rule, &segment.key))
+                .collect(),
+        });
+        if !matched {
+            continue;
+        }
+        for override_fc in &segment.overrides {
+            let replace = match overrides.get(override_fc.name.as_str()) {
+                Some((existing, _)) => priority(override_fc) < priority(existing),
+                None => true,
+            };
+            if replace {
+                overrides.insert(&override_fc.name, (override_fc, &segment.name));
+            }
+        }
+    }
+
+    let identity_key = context.identity.as_ref().map(|identity| {
+        if identity.key.is_empty() {
+            format!("{}_{}", context.environment.key, identity.identifier)
+        } else {
+            identity.key.clone()
+        }
+    });
+
+    let mut flags: Vec<FlagExplanation> = result
+        .flags
+        .values()
+        .map(|flag_result| {
+            let (feature_context, segment_override) = match overrides.get(flag_result.name.as_str())
+            {
+                Some((override_fc, segment_name)) => {
+                    (Some(*override_fc), Some(segment_name.to_string()))
+                }
+                None => (context.features.get(&flag_result.name), None),
+            };
+            let hash_percentage = match (feature_context, &identity_key) {
+                (Some(fc), Some(identity_key)) if !fc.variants.is_empty() && !fc.key.is_empty() => {
+                    Some(hashing::get_hashed_percentage_for_object_ids(
+                        vec![fc.key.as_str(), identity_key.as_str()],
+                        1,
+                    ))
+                }
+                _ => None,
+            };
+            FlagExplanation {
+                feature_name: flag_result.name.clone(),
+                enabled: flag_result.enabled,
+                value: flag_result.value.clone(),
+                reason: flag_result.reason.clone(),
+                segment_override,
+                hash_percentage,
+            }
+        })
+        .collect();
+    flags.sort_by(|a, b| a.feature_name.cmp(&b.feature_name));
+
+    IdentityExplanation {
+        identifier: context
+            .identity
+            .as_ref()
+            .map(|identity| identity.identifier.clone())
+            .unwrap_or_default(),
+        segments,
+        flags,
+    }
+}
+
+fn priority(feature_context: &FeatureContext) -> f64 {
+    feature_context.priority.unwrap_or(f64::INFINITY)
+}
+
+fn explain_rule(
+    context: &EngineEvaluationContext,
+    rule: &SegmentRule,
+    segment_key: &str,
+) -> RuleExplanation {
+    let conditions: Vec<ConditionExplanation> = rule
+        .conditions
+        .iter()
+        .map(|condition| explain_condition(context, condition, segment_key))
+        .collect();
+    let rules: Vec<RuleExplanation> = rule
+        .rules
+        .iter()
+        .map(|nested_rule| explain_rule(context, nested_rule, segment_key))
+        .collect();
+
+    let conditions_matched = conditions.is_empty()
+        || match rule.rule_type {
+            SegmentRuleType::All => conditions.iter().all(|c| c.matched),
+            SegmentRuleType::Any => conditions.iter().any(|c| c.matched),
+            SegmentRuleType::None => !conditions.iter().any(|c| c.matched),
+        };
+    RuleExplanation {
+        rule_type: rule.rule_type.clone(),
+        matched: conditions_matched && rules.iter().all(|r| r.matched),
+        conditions,
+        rules,
+    }
+}
+
+fn explain_condition(
+    context: &EngineEvaluationContext,
+    condition: &Condition,
+    segment_key: &str,
+) -> ConditionExplanation {
+    // Let the engine decide by evaluating a segment made of this condition alone
+    let single_condition_segment = SegmentContext {
+        key: segment_key.to_string(),
+        name: String::new(),
+        metadata: SegmentMetadata::default(),
+        overrides: vec![],
+        rules: vec![SegmentRule {
+            rule_type: SegmentRuleType::All,
+            conditions: vec![condition.clone()],
+            rules: vec![],
+        }],
+    };
+    ConditionExplanation {
+        operator: condition.operator.clone(),
+        property: condition.property.clone(),
+        value: condition.value.clone(),
+        context_value: get_context_value(context, &condition.property),
+        matched: is_context_in_segment(context, &single_condition_segment),
+    }
+}
+
+// Resolves a condition property the way the engine does: dotted JSONPath
+// expressions (e.g. `$.identity.identifier`) first, then trait names
+fn get_context_value(context: &EngineEvaluationContext, property: &str) -> Option<FlagsmithValue> {
+    if let Some(path) = property.strip_prefix("$.") {
+        if let Some(value) = get_value_from_path(context, path) {
+            return Some(value);
+        }
+    }
+    context
+        .identity
+        .as_ref()
+        .and_then(|identity| identity.traits.get(property).cloned())
+}
+
+fn get_value_from_path(context: &EngineEvaluationContext, path: &str) -> Option<FlagsmithValue> {
+    let mut value = serde_json::to_value(context).ok()?;
+    for key in path.split('.') {
+        value = value.get_mut(key)?.take();
+    }
+    match value {
+        serde_json::Value::Null | serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+            None
+        }
+        value => serde_json::from_value(value).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flagsmith::offline_handler::{LocalFileHandler, OfflineHandler};
+    use flagsmith_flag_engine::engine_eval::{add_identity_to_context, environment_to_context};
+    use flagsmith_flag_engine::identities::Trait;
+    use flagsmith_flag_engine::types::FlagsmithValueType;
+
+    fn context_for(identifier: &str, traits: &[Trait]) -> EngineEvaluationContext {
+        let handler = LocalFileHandler::new("tests/fixtures/environment.json").unwrap();
+        let context = environment_to_context(handler.get_environment());
+        add_identity_to_context(&context, identifier, traits)
+    }
+
+    #[test]
+    fn explain_evaluation_reports_condition_values() {
+        // Given
+        let traits = vec![Trait {
+            trait_key: "foo".to_string(),
+            trait_value: FlagsmithValue {
+                value: "baz".to_string(),
+                value_type: FlagsmithValueType::String,
+            },
+        }];
+        let context = context_for("some_identifier", &traits);
+
+        // When
+        let explanation = explain_evaluation(&context);
+
+        // Then
+        let segment = explanation
+            .segments
+            .iter()
+            .find(|segment| segment.name == "Test Segment")
+            .unwrap();
+        assert!(!segment.matched);
+        assert!(!segment.rules[0].matched);
+        let condition = &segment.rules[0].rules[0].conditions[0];
+        assert_eq!(condition.property, "foo");
+        assert_eq!(condition.value.as_string(), "bar");
+        assert_eq!(condition.context_value.as_ref().unwrap().value, "baz");
+        assert!(!condition.matched);
+    }
+
+    #[test]
+    fn explain_evaluation_reports_matched_segments() {
+        // Given
+        let traits = vec![Trait {
+            trait_key: "foo".to_string(),
+            trait_value: FlagsmithValue {
+                value: "bar".to_string(),
+                value_type: FlagsmithValueType::String,
+            },
+        }];
+        let context = context_for("some_identifier", &traits);
+
+        // When
+        let explanation = explain_evaluation(&context);
+
+        // Then
+        let segment = explanation
+            .segments
+            .iter()
+            .find(|segment| segment.name == "Test Segment")
+            .unwrap();
+        assert!(segment.matched);
+        assert!(segment.rules[0].rules[0].conditions[0].matched);
+        assert_eq!(explanation.flags.len(), 1);
+        assert_eq!(explanation.flags[0].reason, "DEFAULT");
+        assert_eq!(explanation.flags[0].segment_override, None);
+    }
+
+    #[test]
+    fn get_context_value_resolves_json_paths() {
+        // Given
+        let context = context_for("some_identifier", &[]);
+
+        // When
+        let value = get_context_value(&context, "$.identity.identifier").unwrap();
+
+        // Then
+        assert_eq!(value.value, "some_identifier");
+        assert_eq!(value.value_type, FlagsmithValueType::String);
+    }
+}
@@ -0,0 +1,130 @@
+use super::models::{Flags, IntoTraitValue, SDKTrait};
+use super::overrides::LocalOverrides;
+use flagsmith_flag_engine::identities::Trait;
+use flagsmith_flag_engine::types::FlagsmithValue;
+
+// Identity to evaluate flags for, accepted by every identity API, e.g.
+// `IdentityContext::new("user_1").with_trait("plan", "premium").transient(true)`.
+// Identifiers, and identifiers paired with traits, convert into contexts:
+// `flagsmith.get_identity_flags_for("user_1")`.
+#[derive(Clone, Debug, Default)]
+pub struct IdentityContext {
+    pub identifier: String,
+    pub traits: Vec<SDKTrait>,
+    // Neither the identity nor its traits are persisted by the Flagsmith API
+    pub transient: bool,
+    // Flags forced for this evaluation only, on top of the client's local overrides
+    overrides: LocalOverrides,
+}
+
+impl IdentityContext {
+    pub fn new(identifier: impl Into<String>) -> Self {
+        IdentityContext {
+            identifier: identifier.into(),
+            ..Default::default()
+        }
+    }
+
+    // A `None` value deletes the trait from the identity, see `IntoTraitValue`
+    pub fn with_trait(
+        mut self,
+        trait_key: impl Into<String>,
+        trait_value: impl IntoTraitValue,
+    ) -> Self {
+        self.traits
+            .push(SDKTrait::from_value(trait_key, trait_value));
+        self
+    }
+
+    pub fn with_traits<T: Into<SDKTrait>>(mut self, traits: impl IntoIterator<Item = T>) -> Self {
+        self.traits.extend(traits.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn transient(mut self, transient: bool) -> Self {
+        self.transient = transient;
+        self
+    }
+
+    // Forces the flag of the given feature in the flags returned for this context
+    pub fn with_override(
+        mut self,
+        feature_name: &str,
+        enabled: bool,
+        value: FlagsmithValue,
+    ) -> Self {
+        self.overrides.set(feature_name, enabled, value);
+        self
+    }
+
+    pub(crate) fn engine_traits(&self) -> Vec<Trait> {
+        self.traits.iter().cloned().map(Trait::from).collect()
+    }
+
+    pub(crate) fn apply_overrides(&self, flags: Flags) -> Flags {
+        self.overrides.apply(flags)
+    }
+}
+
+impl From<&str> for IdentityContext {
+    fn from(identifier: &str) -> Self {
+        IdentityContext::new(identifier)
+    }
+}
+
+impl From<String> for IdentityContext {
+    fn from(identifier: String) -> Self {
+        IdentityContext::new(identifier)
+    }
+}
+
+impl<S: Into<String>> From<(S, Vec<SDKTrait>)> for IdentityContext {
+    fn from((identifier, traits): (S, Vec<SDKTrait>)) -> Self {
+        IdentityContext::new(identifier).with_traits(traits)
+    }
+}
+
+impl<S: Into<String>> From<(S, Vec<Trait>)> for IdentityContext {
+    fn from((identifier, traits): (S, Vec<Trait>)) -> Self {
+        IdentityContext::new(identifier).with_traits(traits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flagsmith_flag_engine::types::FlagsmithValueType;
+
+    #[test]
+    fn identity_context_builder_collects_traits() {
+        // Given
+        let engine_trait = Trait {
+            trait_key: "country".to_string(),
+            trait_value: "fr".into_trait_value(),
+        };
+
+        // When
+        let identity = IdentityContext::new("user_1")
+            .with_trait("plan", "premium")
+            .with_traits(vec![("age", 42)])
+            .with_traits(vec![engine_trait])
+            .transient(true);
+
+        // Then
+        assert_eq!(identity.identifier, "user_1");
+        assert!(identity.transient);
+        let traits = identity.engine_traits();
+        let trait_values: Vec<_> = traits
+            .iter()
+            .map(|t| (t.trait_key.as_str(), t.trait_value.value.as_str()))
+            .collect();
+        assert_eq!(
+            trait_values,
+            vec![("plan", "premium"), ("age", "42"), ("country", "fr")]
+        );
+        assert!(matches!(
+            traits[1].trait_value.value_type,
+            FlagsmithValueType::Integer
+        ));
+    }
+}
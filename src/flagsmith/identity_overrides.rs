@@ -0,0 +1,177 @@
+use flagsmith_flag_engine::engine_eval::context::{
+    ConditionValue, IdentityContext, SegmentContext,
+};
+use flagsmith_flag_engine::engine_eval::{EngineEvaluationContext, SegmentSource};
+use flagsmith_flag_engine::identities::Trait;
+use flagsmith_flag_engine::types::FlagsmithValue;
+use std::collections::HashMap;
+
+// Key of the identity override segment in the context of an overridden identity
+const IDENTITY_OVERRIDES_SEGMENT_KEY: &str = "identity_overrides";
+
+// Identity overrides of an environment, indexed by identifier. The engine maps the
+// overrides to segments listing the overridden identifiers, which every identity
+// would otherwise be evaluated against; with the index, an identity is only
+// evaluated against a segment holding its own overrides and matching only itself.
+#[derive(Debug, Default)]
+pub(crate) struct IdentityOverrideIndex {
+    by_identifier: HashMap<String, SegmentContext>,
+    // Identifiers with an override of the feature, sorted
+    by_feature: HashMap<String, Vec<String>>,
+}
+
+impl IdentityOverrideIndex {
+    // Takes the identity override segments out of the context and indexes them
+    pub(crate) fn extract(context: &mut EngineEvaluationContext) -> Self {
+        let override_keys: Vec<String> = context
+            .segments
+            .iter()
+            .filter(|(_, segment)| segment.metadata.source == SegmentSource::IdentityOverride)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        let mut index = IdentityOverrideIndex::default();
+        for key in override_keys {
+            let mut segment = context.segments.remove(&key).unwrap();
+            // Segments hold a single condition on the identifier, listing the
+            // identities sharing the same overrides
+            let identifiers = match segment.rules[0].conditions[0].value.clone() {
+                ConditionValue::Multiple(identifiers) => identifiers,
+                value => vec![value.as_string()],
+            };
+            segment.rules[0].conditions[0].value = ConditionValue::Multiple(vec![]);
+            for identifier in identifiers {
+                for feature in &segment.overrides {
+                    index
+                        .by_feature
+                        .entry(feature.name.clone())
+                        .or_default()
+                        .push(identifier.clone());
+                }
+                let mut identity_segment = segment.clone();
+                identity_segment.rules[0].conditions[0].value =
+                    ConditionValue::Multiple(vec![identifier.clone()]);
+                index.by_identifier.insert(identifier, identity_segment);
+            }
+        }
+        for identifiers in index.by_feature.values_mut() {
+            identifiers.sort();
+        }
+        index
+    }
+
+    // Copy of the context evaluating the given identity
+    pub(crate) fn identity_context(
+        &self,
+        context: &EngineEvaluationContext,
+        identifier: &str,
+        traits: &[Trait],
+    ) -> EngineEvaluationContext {
+        let mut context = context.clone();
+        let traits = traits
+            .iter()
+            .map(|t| (t.trait_key.clone(), t.trait_value.clone()))
+            .collect();
+        self.set_identity(&mut context, identifier, traits);
+        context
+    }
+
+    // Sets the identity of the context along with its overrides, replacing those of
+    // the previous identity, e.g. to evaluate identities one after the other on a
+    // single copy of the context
+    pub(crate) fn set_identity(
+        &self,
+        context: &mut EngineEvaluationContext,
+        identifier: &str,
+        traits: HashMap<String, FlagsmithValue>,
+    ) {
+        context.identity = Some(IdentityContext {
+            identifier: identifier.to_string(),
+            key: format!("{}_{}", context.environment.key, identifier),
+            traits,
+        });
+        match self.by_identifier.get(identifier) {
+            Some(segment) => {
+                context
+                    .segments
+                    .insert(IDENTITY_OVERRIDES_SEGMENT_KEY.to_string(), segment.clone());
+            }
+            None => {
+                context.segments.remove(IDENTITY_OVERRIDES_SEGMENT_KEY);
+            }
+        }
+    }
+
+    pub(crate) fn identifiers_overriding(&self, feature_name: &str) -> Vec<String> {
+        self.by_feature
+            .get(feature_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flagsmith::environment_builder::{EnvironmentBuilder, FeatureBuilder};
+    use flagsmith_flag_engine::engine::get_evaluation_result;
+    use flagsmith_flag_engine::engine_eval::{add_identity_to_context, environment_to_context};
+
+    fn environment_context() -> EngineEvaluationContext {
+        let environment = EnvironmentBuilder::new("ser.environment_key")
+            .feature(FeatureBuilder::new("feature_1").enabled(true).value("a"))
+            .feature(FeatureBuilder::new("feature_2").value("b"))
+            .identity_override("identity_1", "feature_1", false, "overridden")
+            .identity_override("identity_2", "feature_1", false, "overridden")
+            .identity_override("identity_3", "feature_2", true, "overridden")
+            .build()
+            .unwrap();
+        environment_to_context(environment)
+    }
+
+    #[test]
+    fn extract_indexes_identity_overrides() {
+        // Given
+        let mut context = environment_context();
+
+        // When
+        let index = IdentityOverrideIndex::extract(&mut context);
+
+        // Then
+        assert!(context
+            .segments
+            .values()
+            .all(|segment| segment.metadata.source != SegmentSource::IdentityOverride));
+        assert_eq!(
+            index.identifiers_overriding("feature_1"),
+            vec!["identity_1", "identity_2"]
+        );
+        assert_eq!(
+            index.identifiers_overriding("feature_2"),
+            vec!["identity_3"]
+        );
+        assert!(index.identifiers_overriding("unknown").is_empty());
+    }
+
+    #[test]
+    fn identity_context_evaluates_as_the_full_context() {
+        // Given
+        let full_context = environment_context();
+        let mut context = full_context.clone();
+        let index = IdentityOverrideIndex::extract(&mut context);
+
+        for identifier in ["identity_1", "identity_3", "not_overridden"] {
+            // When
+            let result = get_evaluation_result(&index.identity_context(&context, identifier, &[]));
+
+            // Then
+            let expected =
+                get_evaluation_result(&add_identity_to_context(&full_context, identifier, &[]));
+            for (feature_name, flag) in &expected.flags {
+                assert_eq!(result.flags[feature_name].enabled, flag.enabled);
+                assert_eq!(result.flags[feature_name].value, flag.value);
+            }
+            assert_eq!(result.segments.len(), expected.segments.len());
+        }
+    }
+}
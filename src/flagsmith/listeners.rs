@@ -0,0 +1,226 @@
+use super::models::{Flag, Flags};
+use log::warn;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+type EnvironmentUpdatedCallback = Arc<dyn Fn(&EnvironmentFlagsDiff) + Send + Sync>;
+type FlagChangedCallback = Arc<dyn Fn(&FlagChange) + Send + Sync>;
+
+// Change to a single environment flag. `previous` is `None` for features that were
+// added, `current` is `None` for features that were removed.
+#[derive(Clone, Debug)]
+pub struct FlagChange {
+    pub feature_name: String,
+    pub previous: Option<Flag>,
+    pub current: Option<Flag>,
+}
+
+impl FlagChange {
+    pub fn is_added(&self) -> bool {
+        self.previous.is_none()
+    }
+    pub fn is_removed(&self) -> bool {
+        self.current.is_none()
+    }
+    pub fn enabled_changed(&self) -> bool {
+        match (&self.previous, &self.current) {
+            (Some(previous), Some(current)) => previous.enabled != current.enabled,
+            _ => false,
+        }
+    }
+    pub fn value_changed(&self) -> bool {
+        match (&self.previous, &self.current) {
+            (Some(previous), Some(current)) => previous.value != current.value,
+            _ => false,
+        }
+    }
+}
+
+// Differences between the environment flags before and after a refresh
+#[derive(Clone, Debug, Default)]
+pub struct EnvironmentFlagsDiff {
+    pub changes: Vec<FlagChange>,
+}
+
+impl EnvironmentFlagsDiff {
+    // Compares environment flags. With no previous flags, i.e. on the first
+    // successful refresh, every flag is reported as added.
+    pub fn between(previous: Option<&Flags>, current: &Flags) -> EnvironmentFlagsDiff {
+        let mut changes = Vec::new();
+        for (feature_name, flag) in current.as_map() {
+            let previous_flag = previous.and_then(|flags| flags.as_map().get(feature_name));
+            let changed = match previous_flag {
+                Some(previous_flag) => {
+                    previous_flag.enabled != flag.enabled || previous_flag.value != flag.value
+                }
+                None => true,
+            };
+            if changed {
+                changes.push(FlagChange {
+                    feature_name: feature_name.clone(),
+                    previous: previous_flag.cloned(),
+                    current: Some(flag.clone()),
+                });
+            }
+        }
+        if let Some(previous) = previous {
+            for (feature_name, flag) in previous.as_map() {
+                if !current.as_map().contains_key(feature_name) {
+                    changes.push(FlagChange {
+                        feature_name: feature_name.clone(),
+                        previous: Some(flag.clone()),
+                        current: None,
+                    });
+                }
+            }
+        }
+        changes.sort_by(|a, b| a.feature_name.cmp(&b.feature_name));
+        EnvironmentFlagsDiff { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn get(&self, feature_name: &str) -> Option<&FlagChange> {
+        self.changes
+            .iter()
+            .find(|change| change.feature_name == feature_name)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Listeners {
+    environment_updated: Vec<EnvironmentUpdatedCallback>,
+    flag_changed: Vec<(String, FlagChangedCallback)>,
+}
+
+impl Listeners {
+    pub(crate) fn add_environment_updated(&mut self, callback: EnvironmentUpdatedCallback) {
+        self.environment_updated.push(callback);
+    }
+
+    pub(crate) fn add_flag_changed(&mut self, feature_name: String, callback: FlagChangedCallback) {
+        self.flag_changed.push((feature_name, callback));
+    }
+
+    // Calls the listeners concerned by the changes, if any. Must not be called
+    // while holding the datastore lock so that callbacks can use the client.
+    pub(crate) fn notify(&self, previous: Option<&Flags>, current: &Flags) {
+        if self.environment_updated.is_empty() && self.flag_changed.is_empty() {
+            return;
+        }
+        let diff = EnvironmentFlagsDiff::between(previous, current);
+        if diff.is_empty() {
+            return;
+        }
+        for callback in &self.environment_updated {
+            call_listener(|| callback(&diff));
+        }
+        for (feature_name, callback) in &self.flag_changed {
+            if let Some(change) = diff.get(feature_name) {
+                call_listener(|| callback(change));
+            }
+        }
+    }
+}
+
+// Keep a panicking listener from taking down the polling thread
+fn call_listener<F: FnOnce()>(callback: F) {
+    if panic::catch_unwind(AssertUnwindSafe(callback)).is_err() {
+        warn!("Environment listener panicked");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn flags(flags_json: serde_json::Value) -> Flags {
+        Flags::from_api_flags(flags_json.as_array().unwrap(), None, None).unwrap()
+    }
+
+    fn flag_json(name: &str, enabled: bool, value: &str) -> serde_json::Value {
+        serde_json::json!({
+            "feature": {"id": 1, "name": name},
+            "feature_state_value": value,
+            "enabled": enabled
+        })
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed_flags() {
+        // Given
+        let previous = flags(serde_json::json!([
+            flag_json("unchanged", true, "a"),
+            flag_json("kill_switch", true, "a"),
+            flag_json("removed", true, "a"),
+            flag_json("value", true, "a"),
+        ]));
+        let current = flags(serde_json::json!([
+            flag_json("unchanged", true, "a"),
+            flag_json("kill_switch", false, "a"),
+            flag_json("value", true, "b"),
+            flag_json("added", true, "a"),
+        ]));
+
+        // When
+        let diff = EnvironmentFlagsDiff::between(Some(&previous), &current);
+
+        // Then
+        assert_eq!(diff.changes.len(), 4);
+        assert!(diff.get("unchanged").is_none());
+        assert!(diff.get("added").unwrap().is_added());
+        assert!(diff.get("removed").unwrap().is_removed());
+        let kill_switch = diff.get("kill_switch").unwrap();
+        assert!(kill_switch.enabled_changed());
+        assert!(!kill_switch.value_changed());
+        let value = diff.get("value").unwrap();
+        assert!(value.value_changed());
+        assert!(!value.enabled_changed());
+    }
+
+    #[test]
+    fn diff_reports_all_flags_as_added_without_previous_flags() {
+        // Given
+        let current = flags(serde_json::json!([flag_json("feature", true, "a")]));
+
+        // When
+        let diff = EnvironmentFlagsDiff::between(None, &current);
+
+        // Then
+        assert_eq!(diff.changes.len(), 1);
+        assert!(diff.changes[0].is_added());
+    }
+
+    #[test]
+    fn notify_only_calls_flag_listeners_for_changed_flags() {
+        // Given
+        let previous = flags(serde_json::json!([
+            flag_json("feature_1", true, "a"),
+            flag_json("feature_2", true, "a"),
+        ]));
+        let current = flags(serde_json::json!([
+            flag_json("feature_1", false, "a"),
+            flag_json("feature_2", true, "a"),
+        ]));
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut listeners = Listeners::default();
+        for feature_name in ["feature_1", "feature_2"] {
+            let calls = Arc::clone(&calls);
+            listeners.add_flag_changed(
+                feature_name.to_string(),
+                Arc::new(move |change: &FlagChange| {
+                    calls.lock().unwrap().push(change.feature_name.clone())
+                }),
+            );
+        }
+
+        // When
+        listeners.notify(Some(&previous), &current);
+
+        // Then
+        assert_eq!(*calls.lock().unwrap(), vec!["feature_1".to_string()]);
+    }
+}
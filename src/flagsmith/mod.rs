@@ -1,29 +1,60 @@
 use self::analytics::AnalyticsProcessor;
+use self::explain::IdentityExplanation;
+use self::identity::IdentityContext;
+use self::identity_overrides::IdentityOverrideIndex;
+use self::listeners::{EnvironmentFlagsDiff, FlagChange, Listeners};
 use self::models::{Flag, Flags};
+use self::overrides::LocalOverrides;
+use self::request_options::{EvaluationMode, RequestOptions, ScopedFlagsmith};
+use self::status::{ClientMode, ClientStatus, RefreshStatus};
+use self::transport::{ClientCertificate, HttpResponse, HttpTransport, ProxyOptions};
 use super::error;
 use flagsmith_flag_engine::engine::get_evaluation_result;
 use flagsmith_flag_engine::engine_eval::{
-    add_identity_to_context, environment_to_context, EngineEvaluationContext, SegmentSource,
+    environment_to_context, EngineEvaluationContext, SegmentSource,
 };
 use flagsmith_flag_engine::environments::builders::build_environment_struct;
 use flagsmith_flag_engine::environments::Environment;
 use flagsmith_flag_engine::identities::Trait;
-use flagsmith_flag_engine::segments::Segment;
+use flagsmith_flag_engine::segments::constants::{ALL_RULE, IN};
+use flagsmith_flag_engine::segments::{Segment, SegmentCondition, SegmentRule};
+use flagsmith_flag_engine::types::FlagsmithValue;
+use http::header::{self, HeaderMap};
+use http::Method;
 use log::debug;
 use models::SDKTrait;
-use reqwest::header::{self, HeaderMap};
 use serde_json::json;
 use std::collections::HashMap;
-use std::sync::mpsc::{self, SyncSender, TryRecvError};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
 use std::sync::{Arc, Mutex};
-use std::{thread, time::Duration};
+use std::thread;
+use std::time::{Duration, Instant};
 
 mod analytics;
 
+pub mod diff;
+pub mod environment_builder;
+pub mod explain;
+pub mod identity;
+mod identity_overrides;
+pub mod listeners;
 pub mod models;
 pub mod offline_handler;
+pub mod overrides;
+pub mod pool;
+pub mod provider;
+#[cfg(feature = "relay")]
+pub mod relay;
+pub mod request_options;
+pub mod status;
+mod telemetry;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod transport;
 
 const DEFAULT_API_URL: &str = "https://edge.api.flagsmith.com/api/v1/";
+const IDENTITY_OVERRIDES_SEGMENT_NAME: &str = "identity_overrides";
+const ENVIRONMENT_WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 // Get the SDK version from Cargo.toml at compile time, or default to "unknown"
 fn get_user_agent() -> String {
@@ -41,6 +72,33 @@ pub struct FlagsmithOptions {
     pub default_flag_handler: Option<fn(&str) -> Flag>,
     pub offline_handler: Option<Box<dyn offline_handler::OfflineHandler + Send + Sync>>,
     pub offline_mode: bool,
+    // Serve environment flags from memory in remote evaluation mode, refreshing
+    // them in the background every `environment_refresh_interval_mills`
+    pub enable_environment_flags_cache: bool,
+    // Number of threads used by `get_identities_flags`, evaluating the identities in
+    // local evaluation mode and sending their requests in remote evaluation mode
+    pub bulk_evaluation_parallelism: usize,
+    // In local evaluation mode, return an error rather than falling back to the API
+    // while the environment has not been loaded
+    pub strict_local_evaluation: bool,
+    // JSON file of flags forced locally, e.g. `{"my_feature": {"enabled": true, "value": 1}}`.
+    // Defaults to the file named by the `FLAGSMITH_LOCAL_OVERRIDES_PATH` environment variable.
+    pub local_overrides_path: Option<String>,
+    // Sends the requests to the Flagsmith API, including analytics. Defaults to a
+    // reqwest client (`reqwest` feature), and must be set without it.
+    pub transport: Option<Arc<dyn HttpTransport>>,
+    // The options below configure the default transport, and cannot be used with
+    // `transport`. Requests, including analytics, go through the proxy if set.
+    pub proxy: Option<ProxyOptions>,
+    // PEM encoded certificates, or bundles, trusted along with the system roots,
+    // e.g. an internal certificate authority
+    pub root_certificates: Vec<Vec<u8>>,
+    pub client_certificate: Option<ClientCertificate>,
+    // Time allowed to connect, within `request_timeout_seconds`
+    pub connect_timeout_seconds: Option<u64>,
+    // Prepended to identifiers before they are hashed into `tracing` spans, so that
+    // the hashes cannot be matched against those of guessed identifiers
+    pub identifier_hash_salt: String,
 }
 
 impl Default for FlagsmithOptions {
@@ -55,71 +113,203 @@ impl Default for FlagsmithOptions {
             default_flag_handler: None,
             offline_handler: None,
             offline_mode: false,
+            enable_environment_flags_cache: false,
+            bulk_evaluation_parallelism: 1,
+            strict_local_evaluation: false,
+            local_overrides_path: None,
+            transport: None,
+            proxy: None,
+            root_certificates: vec![],
+            client_certificate: None,
+            connect_timeout_seconds: None,
+            identifier_hash_salt: String::new(),
         }
     }
 }
 
 pub struct Flagsmith {
-    client: reqwest::blocking::Client,
+    client: ApiClient,
     environment_flags_url: String,
     identities_url: String,
     environment_url: String,
-    options: FlagsmithOptions,
+    options: Arc<FlagsmithOptions>,
     datastore: Arc<Mutex<DataStore>>,
     analytics_processor: Option<AnalyticsProcessor>,
     _polling_thread_tx: SyncSender<u32>, // to trigger polling manager shutdown
 }
 
-struct DataStore {
-    environment: Option<Environment>,
-    evaluation_context: Option<EngineEvaluationContext>,
+// HTTP client bound to one environment. The transport, and with it the connection
+// pool, can be shared between environments, see `FlagsmithPool`.
+#[derive(Clone)]
+struct ApiClient {
+    transport: Arc<dyn HttpTransport>,
+    // `custom_headers` along with the SDK headers and the environment key
+    headers: HeaderMap,
+    timeout: Duration,
 }
 
-impl Flagsmith {
-    pub fn new(environment_key: String, flagsmith_options: FlagsmithOptions) -> Self {
+impl ApiClient {
+    fn new(
+        transport: Arc<dyn HttpTransport>,
+        flagsmith_options: &FlagsmithOptions,
+        environment_key: &str,
+    ) -> Self {
         let mut headers = flagsmith_options.custom_headers.clone();
         headers.insert(
-            "X-Environment-Key",
-            header::HeaderValue::from_str(&environment_key).unwrap(),
+            header::CONTENT_TYPE,
+            header::HeaderValue::from_static("application/json"),
         );
-        headers.insert("Content-Type", "application/json".parse().unwrap());
         headers.insert(
             header::USER_AGENT,
             header::HeaderValue::from_str(&get_user_agent()).unwrap(),
         );
-        let timeout = Duration::from_secs(flagsmith_options.request_timeout_seconds);
-        let client = reqwest::blocking::Client::builder()
-            .default_headers(headers.clone())
-            .timeout(timeout)
-            .build()
-            .unwrap();
+        headers.insert(
+            "X-Environment-Key",
+            header::HeaderValue::from_str(environment_key).unwrap(),
+        );
+        ApiClient {
+            transport,
+            headers,
+            timeout: Duration::from_secs(flagsmith_options.request_timeout_seconds),
+        }
+    }
+
+    fn send(
+        &self,
+        method: Method,
+        url: String,
+        body: Option<Vec<u8>>,
+        options: &RequestOptions,
+    ) -> Result<HttpResponse, error::Error> {
+        let mut headers = self.headers.clone();
+        for (name, value) in &options.headers {
+            headers.insert(name, value.clone());
+        }
+        self.transport.send(transport::HttpRequest {
+            method,
+            url,
+            headers,
+            body,
+            timeout: options.timeout.unwrap_or(self.timeout),
+        })
+    }
+}
+
+struct DataStore {
+    environment: Option<Arc<Environment>>,
+    // Environment document as served, see `get_environment_document_json`
+    environment_document: Option<Arc<serde_json::Value>>,
+    // Holds no identity override, see `identity_overrides`
+    evaluation_context: Option<Arc<EngineEvaluationContext>>,
+    identity_overrides: Arc<IdentityOverrideIndex>,
+    environment_flags: Option<Flags>,
+    listeners: Listeners,
+    refresh_status: RefreshStatus,
+    overrides: LocalOverrides,
+}
+
+impl DataStore {
+    // Swaps in the new environment flags. The returned listeners must be notified
+    // of the change, against the returned previous flags, once the lock is released.
+    fn replace_environment_flags(&mut self, flags: Flags) -> (Listeners, Option<Flags>) {
+        let previous = self.environment_flags.replace(flags);
+        (self.listeners.clone(), previous)
+    }
+}
+
+impl Flagsmith {
+    pub fn new(environment_key: String, flagsmith_options: FlagsmithOptions) -> Self {
+        if let Err(e) = validate_options(&flagsmith_options) {
+            panic!("{}", e.msg)
+        }
+        let transport = build_transport(&flagsmith_options);
+        let (flagsmith, rx) =
+            Flagsmith::with_transport(environment_key, Arc::new(flagsmith_options), transport)
+                .unwrap_or_else(|e| panic!("{}", e.msg));
+        flagsmith.load_environment();
+
+        // Create a thread to update environment document
+        // If enabled
+        let environment_refresh_interval_mills =
+            flagsmith.options.environment_refresh_interval_mills;
+        let client = flagsmith.client.clone();
+        let ds = Arc::clone(&flagsmith.datastore);
+        let analytics_processor = flagsmith.analytics_processor.clone();
+        let default_flag_handler = flagsmith.options.default_flag_handler;
+
+        if flagsmith.options.enable_local_evaluation {
+            let environment_url = flagsmith.environment_url.clone();
+            spawn_polling_thread(rx, environment_refresh_interval_mills, move || {
+                if let Err(e) = update_environment(
+                    &client,
+                    &ds,
+                    &environment_url,
+                    &analytics_processor,
+                    default_flag_handler,
+                ) {
+                    log::warn!(
+                        "Failed to update environment: {}. Will retry on next interval.",
+                        e
+                    );
+                }
+            });
+        } else if flagsmith.options.enable_environment_flags_cache {
+            let environment_flags_url = flagsmith.environment_flags_url.clone();
+            spawn_polling_thread(rx, environment_refresh_interval_mills, move || {
+                if let Err(e) = update_environment_flags(
+                    &client,
+                    &ds,
+                    &environment_flags_url,
+                    &analytics_processor,
+                    default_flag_handler,
+                ) {
+                    log::warn!(
+                        "Failed to update environment flags: {}. Serving last known flags.",
+                        e
+                    );
+                }
+            });
+        }
+        flagsmith
+    }
 
+    // Builds a client sending its requests through `transport`, without loading the
+    // environment nor refreshing it. The returned receiver disconnects once the
+    // client is dropped, to shut down whatever refreshes it. The options must have
+    // been checked with `validate_options`; errors are for the environment key and
+    // the local overrides file.
+    pub(crate) fn with_transport(
+        environment_key: String,
+        flagsmith_options: Arc<FlagsmithOptions>,
+        transport: Arc<dyn HttpTransport>,
+    ) -> Result<(Self, Receiver<u32>), error::Error> {
         let environment_flags_url = format!("{}flags/", flagsmith_options.api_url);
         let identities_url = format!("{}identities/", flagsmith_options.api_url);
         let environment_url = format!("{}environment-document/", flagsmith_options.api_url);
 
-        if flagsmith_options.offline_mode && flagsmith_options.offline_handler.is_none() {
-            panic!("offline_handler must be set to use offline_mode")
-        }
-        if flagsmith_options.default_flag_handler.is_some()
-            && flagsmith_options.offline_handler.is_some()
-        {
-            panic!("default_flag_handler cannot be used with offline_handler")
-        }
-        if flagsmith_options.enable_local_evaluation && flagsmith_options.offline_handler.is_some()
-        {
-            panic!("offline_handler cannot be used with local evaluation")
-        }
-        if flagsmith_options.enable_local_evaluation && !environment_key.starts_with("ser.") {
-            panic!("In order to use local evaluation, please use a server-side environment key (starts with 'ser.')")
-        }
+        validate_environment_key(&flagsmith_options, &environment_key)?;
+
+        let local_overrides_path = flagsmith_options
+            .local_overrides_path
+            .clone()
+            .or_else(|| std::env::var(overrides::LOCAL_OVERRIDES_PATH_ENV_VAR).ok());
+        let overrides = match local_overrides_path {
+            Some(path) => LocalOverrides::from_file(&path).map_err(|e| {
+                error::Error::new(
+                    error::ErrorKind::FlagsmithClientError,
+                    format!("Failed to load local overrides from {}: {}", path, e),
+                )
+            })?,
+            None => LocalOverrides::default(),
+        };
+
+        let client = ApiClient::new(transport, &flagsmith_options, &environment_key);
 
         // Initialize analytics processor
         let analytics_processor = match flagsmith_options.enable_analytics {
-            true => Some(AnalyticsProcessor::new(
+            true => Some(AnalyticsProcessor::with_client(
+                client.clone(),
                 flagsmith_options.api_url.clone(),
-                headers,
-                timeout,
                 None,
             )),
             false => None,
@@ -129,80 +319,282 @@ impl Flagsmith {
         // to share it safely between threads
         let ds = Arc::new(Mutex::new(DataStore {
             environment: None,
+            environment_document: None,
             evaluation_context: None,
+            identity_overrides: Arc::default(),
+            environment_flags: None,
+            listeners: Listeners::default(),
+            refresh_status: RefreshStatus::default(),
+            overrides,
         }));
         let (tx, rx) = mpsc::sync_channel::<u32>(1);
 
         let flagsmith = Flagsmith {
-            client: client.clone(),
+            client,
             environment_flags_url,
-            environment_url: environment_url.clone(),
+            environment_url,
             identities_url,
             options: flagsmith_options,
-            datastore: Arc::clone(&ds),
+            datastore: ds,
             analytics_processor,
             _polling_thread_tx: tx,
         };
+        Ok((flagsmith, rx))
+    }
 
-        if flagsmith.options.offline_handler.is_some() {
-            let mut data = flagsmith.datastore.lock().unwrap();
-            let environment = flagsmith
-                .options
-                .offline_handler
-                .as_ref()
-                .unwrap()
-                .get_environment();
+    // Loads the environment from the offline handler or, failing that, runs a first
+    // refresh. A failed refresh is only logged, for the background refreshes to retry.
+    pub(crate) fn load_environment(&self) {
+        if let Some(offline_handler) = &self.options.offline_handler {
+            set_environment(
+                &self.datastore,
+                offline_handler.get_environment(),
+                offline_handler.get_environment_document(),
+                &self.analytics_processor,
+                self.options.default_flag_handler,
+            );
+        } else if let Err(e) = self.refresh() {
+            log::warn!(
+                "Failed to fetch environment on initialization: {}. Will retry in background.",
+                e
+            );
+        }
+    }
 
-            // Create evaluation context from offline environment
-            let eval_context = environment_to_context(environment.clone());
-            data.evaluation_context = Some(eval_context);
+    // Fetches the environment document in local evaluation mode, or the environment
+    // flags with `enable_environment_flags_cache`. Does nothing in other modes.
+    pub(crate) fn refresh(&self) -> Result<(), error::Error> {
+        if self.options.enable_local_evaluation {
+            update_environment(
+                &self.client,
+                &self.datastore,
+                &self.environment_url,
+                &self.analytics_processor,
+                self.options.default_flag_handler,
+            )
+        } else if self.options.enable_environment_flags_cache {
+            update_environment_flags(
+                &self.client,
+                &self.datastore,
+                &self.environment_flags_url,
+                &self.analytics_processor,
+                self.options.default_flag_handler,
+            )
+        } else {
+            Ok(())
+        }
+    }
 
-            data.environment = Some(environment);
+    //Returns `Flags` struct holding all the flags for the current environment.
+    // Environment flags are evaluated once per environment update in local evaluation
+    // and offline modes. With `enable_environment_flags_cache`, flags are served from
+    // memory and only fetched synchronously if the cache has never been filled.
+    pub fn get_environment_flags(&self) -> Result<models::Flags, error::Error> {
+        self.get_environment_flags_with(&RequestOptions::default())
+    }
+
+    // Returns a client making its calls with the given timeout, headers and
+    // evaluation mode, sharing this client's connections and environment
+    pub fn with_request_options(&self, options: RequestOptions) -> ScopedFlagsmith<'_> {
+        ScopedFlagsmith {
+            flagsmith: self,
+            options,
         }
+    }
 
-        // Create a thread to update environment document
-        // If enabled
-        let environment_refresh_interval_mills =
-            flagsmith.options.environment_refresh_interval_mills;
+    fn get_environment_flags_with(&self, options: &RequestOptions) -> Result<Flags, error::Error> {
+        let span = telemetry::environment_flags_span(self.mode());
+        let result = span.record_flags(self.environment_flags(options));
+        telemetry::record_evaluation("environment", self.mode(), result.is_ok());
+        result
+    }
 
-        if flagsmith.options.enable_local_evaluation {
-            // Update environment once...
-            if let Err(e) = update_environment(&client, &ds, &environment_url) {
-                log::warn!(
-                    "Failed to fetch environment on initialization: {}. Will retry in background.",
-                    e
-                );
+    fn environment_flags(&self, options: &RequestOptions) -> Result<Flags, error::Error> {
+        let data = self.datastore.lock().unwrap();
+        // Environment flags are precomputed from the environment document, or cached
+        let flags = match options.evaluation_mode {
+            None => data.environment_flags.as_ref(),
+            Some(EvaluationMode::Local) => data
+                .evaluation_context
+                .as_ref()
+                .and(data.environment_flags.as_ref()),
+            Some(EvaluationMode::Remote) => None,
+        };
+        if self.use_local_evaluation(options, flags.is_some())? {
+            return Ok(data.overrides.apply(flags.unwrap().clone()));
+        }
+        drop(data);
+
+        let result = self.get_environment_flags_from_api(options);
+        if self.options.enable_environment_flags_cache && options.evaluation_mode.is_none() {
+            let mut data = self.datastore.lock().unwrap();
+            match &result {
+                Ok(flags) => {
+                    data.refresh_status.record_success(None);
+                    let (listeners, previous) = data.replace_environment_flags(flags.clone());
+                    drop(data);
+                    listeners.notify(previous.as_ref(), flags);
+                }
+                Err(e) => data.refresh_status.record_failure(e),
             }
+        }
+        self.default_handler_if_err(result)
+            .map(|flags| self.apply_overrides(flags))
+    }
 
-            // ...and continue updating in the background
-            let ds = Arc::clone(&ds);
-            thread::spawn(move || loop {
-                match rx.try_recv() {
-                    Ok(_) | Err(TryRecvError::Disconnected) => {
-                        debug!("shutting down polling manager");
-                        break;
-                    }
-                    Err(TryRecvError::Empty) => {}
-                }
-                thread::sleep(Duration::from_millis(environment_refresh_interval_mills));
-                if let Err(e) = update_environment(&client, &ds, &environment_url) {
-                    log::warn!(
-                        "Failed to update environment: {}. Will retry on next interval.",
-                        e
-                    );
-                }
-            });
+    // Forces the given flag, for every identity, on top of the flags served by the
+    // client. Overridden flags are reported by `Flags::is_overridden` and are not sent to
+    // analytics.
+    pub fn set_override(&self, feature_name: &str, enabled: bool, value: FlagsmithValue) {
+        let mut data = self.datastore.lock().unwrap();
+        data.overrides.set(feature_name, enabled, value);
+    }
+
+    pub fn clear_override(&self, feature_name: &str) {
+        let mut data = self.datastore.lock().unwrap();
+        data.overrides.remove(feature_name);
+    }
+
+    // Removes all local overrides, including the ones loaded from the overrides file
+    pub fn clear_overrides(&self) {
+        let mut data = self.datastore.lock().unwrap();
+        data.overrides.clear();
+    }
+
+    fn apply_overrides(&self, flags: Flags) -> Flags {
+        self.datastore.lock().unwrap().overrides.apply(flags)
+    }
+
+    // Key the client was created with, sent as the `X-Environment-Key` header
+    #[cfg(feature = "relay")]
+    pub(crate) fn environment_key(&self) -> Option<&str> {
+        self.client
+            .headers
+            .get("X-Environment-Key")
+            .and_then(|value| value.to_str().ok())
+    }
+
+    // Environment document last loaded in local evaluation and offline modes, if any
+    pub fn get_environment_document(&self) -> Option<Environment> {
+        self.datastore
+            .lock()
+            .unwrap()
+            .environment
+            .as_deref()
+            .cloned()
+    }
+
+    // Same as `get_environment_document`, as the JSON document served by the API or
+    // read by the offline handler, keeping the fields the engine model drops such as
+    // `updated_at`. Falls back to the serialized model if the handler has no document.
+    pub fn get_environment_document_json(&self) -> Option<Arc<serde_json::Value>> {
+        self.datastore.lock().unwrap().environment_document.clone()
+    }
+
+    // Returns the mode the client runs in along with the outcome of the environment
+    // refreshes, e.g. to back a readiness probe with `ClientStatus::is_ready`
+    pub fn status(&self) -> ClientStatus {
+        let refresh_status = self.datastore.lock().unwrap().refresh_status.clone();
+        ClientStatus {
+            mode: self.mode(),
+            last_successful_refresh: refresh_status.last_successful_refresh,
+            last_error: refresh_status.last_error,
+            consecutive_failures: refresh_status.consecutive_failures,
+            environment_updated_at: refresh_status.environment_updated_at,
+            analytics_queue_depth: self
+                .analytics_processor
+                .as_ref()
+                .map_or(0, |processor| processor.queue_depth()),
         }
-        return flagsmith;
     }
-    //Returns `Flags` struct holding all the flags for the current environment.
-    pub fn get_environment_flags(&self) -> Result<models::Flags, error::Error> {
-        let data = self.datastore.lock().unwrap();
-        if data.evaluation_context.is_some() {
-            let eval_context = data.evaluation_context.as_ref().unwrap();
-            return Ok(self.get_environment_flags_from_document(eval_context));
+
+    // Blocks until the environment has been loaded, i.e. until the client is ready
+    // as reported by `status`, or the timeout expires. Returns immediately in remote
+    // evaluation mode.
+    pub fn wait_for_environment(&self, timeout: Duration) -> Result<(), error::Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let refresh_status = self.datastore.lock().unwrap().refresh_status.clone();
+            if self.mode() == ClientMode::Remote || refresh_status.last_successful_refresh.is_some()
+            {
+                return Ok(());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                let reason = refresh_status
+                    .last_error
+                    .map(|e| format!(" Last error: {}", e))
+                    .unwrap_or_default();
+                return Err(error::Error::new(
+                    error::ErrorKind::FlagsmithClientError,
+                    format!("Timed out waiting for the environment to load.{}", reason),
+                ));
+            }
+            thread::sleep(ENVIRONMENT_WAIT_POLL_INTERVAL.min(deadline - now));
         }
-        return self.default_handler_if_err(self.get_environment_flags_from_api());
+    }
+
+    // Whether to serve a call from the environment document (or the environment flags
+    // cache) rather than the API, given whether it has been loaded
+    fn use_local_evaluation(
+        &self,
+        options: &RequestOptions,
+        environment_loaded: bool,
+    ) -> Result<bool, error::Error> {
+        match options.evaluation_mode {
+            None if environment_loaded => Ok(true),
+            None if self.options.strict_local_evaluation => Err(error::Error::new(
+                error::ErrorKind::FlagsmithClientError,
+                "Environment not loaded and strict_local_evaluation forbids falling back to the API."
+                    .to_string(),
+            )),
+            None => Ok(false),
+            Some(EvaluationMode::Local) if environment_loaded => Ok(true),
+            Some(EvaluationMode::Local) => Err(error::Error::new(
+                error::ErrorKind::FlagsmithClientError,
+                "Local evaluation requested but the environment is not loaded.".to_string(),
+            )),
+            Some(EvaluationMode::Remote) if self.options.offline_mode => Err(error::Error::new(
+                error::ErrorKind::FlagsmithClientError,
+                "Remote evaluation requested in offline mode.".to_string(),
+            )),
+            Some(EvaluationMode::Remote) => Ok(false),
+        }
+    }
+
+    fn mode(&self) -> ClientMode {
+        if self.options.offline_handler.is_some() {
+            ClientMode::Offline
+        } else if self.options.enable_local_evaluation {
+            ClientMode::LocalEvaluation
+        } else if self.options.enable_environment_flags_cache {
+            ClientMode::CachedRemote
+        } else {
+            ClientMode::Remote
+        }
+    }
+
+    // Registers a callback called with the changes to the environment flags whenever
+    // a refresh changes them. The environment is loaded by `new` before any callback
+    // can be registered, so the first successful load is only reported if the
+    // initial one failed and a later refresh succeeds.
+    pub fn on_environment_updated<F>(&self, callback: F)
+    where
+        F: Fn(&EnvironmentFlagsDiff) + Send + Sync + 'static,
+    {
+        let mut data = self.datastore.lock().unwrap();
+        data.listeners.add_environment_updated(Arc::new(callback));
+    }
+
+    // Registers a callback called whenever a refresh adds, removes, enables, disables
+    // or changes the value of the given environment flag.
+    pub fn on_flag_changed<F>(&self, feature_name: &str, callback: F)
+    where
+        F: Fn(&FlagChange) + Send + Sync + 'static,
+    {
+        let mut data = self.datastore.lock().unwrap();
+        data.listeners
+            .add_flag_changed(feature_name.to_string(), Arc::new(callback));
     }
 
     // Returns all the flags for the current environment for a given identity. Will also
@@ -210,19 +602,14 @@ impl Flagsmith {
     // trait with a value of None will remove the trait from the identity if it exists.
     // # Example
     // ```
-    // use flagsmith_flag_engine::identities::Trait;
-    // use flagsmith::{Flagsmith, FlagsmithOptions};
-    // use flagsmith_flag_engine::types::{FlagsmithValue, FlagsmithValueType};
+    // use flagsmith::{traits, Flagsmith, FlagsmithOptions};
     // const ENVIRONMENT_KEY: &str = "YOUR_ENVIRONMENT_KEY";
     // fn main(){
     //     let flagsmith_options = FlagsmithOptions::default();
-    //     let traits = vec![Trait{trait_key:"random_key".to_string(), trait_value: FlagsmithValue{value:"10.1".to_string(), value_type:FlagsmithValueType::Float}},
-    //                       Trait{trait_key:"another_random_key".to_string(), trait_value: FlagsmithValue{value:"false".to_string(), value_type:FlagsmithValueType::Bool}},
-    //                       Trait{trait_key:"another_random_key".to_string(), trait_value: FlagsmithValue{value:"".to_string(), value_type:FlagsmithValueType::None}}
-    //     ];
-    //     let mut flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    //     let traits = traits!("random_key" => 10.1, "another_random_key" => false, "deleted_key" => None::<bool>);
+    //     let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
 
-    //     let flags = flagsmith.get_identity_flags("user_identifier".to_string(), traits);
+    //     let flags = flagsmith.get_identity_flags("user_identifier", Some(traits), None);
     // }
     //```
     pub fn get_identity_flags(
@@ -231,52 +618,262 @@ impl Flagsmith {
         traits: Option<Vec<SDKTrait>>,
         transient: Option<bool>,
     ) -> Result<Flags, error::Error> {
+        self.get_identity_flags_for(
+            IdentityContext::new(identifier)
+                .with_traits(traits.unwrap_or_default())
+                .transient(transient.unwrap_or(false)),
+        )
+    }
+
+    // Same as `get_identity_flags`, for an identity given as an `IdentityContext`,
+    // or anything converting into one, e.g. `get_identity_flags_for("user_1")`.
+    // The overrides of the context are applied on top of the local overrides.
+    pub fn get_identity_flags_for(
+        &self,
+        identity: impl Into<IdentityContext>,
+    ) -> Result<Flags, error::Error> {
+        self.get_identity_flags_with(identity.into(), &RequestOptions::default())
+    }
+
+    fn get_identity_flags_with(
+        &self,
+        identity: IdentityContext,
+        options: &RequestOptions,
+    ) -> Result<Flags, error::Error> {
+        let span = telemetry::identity_flags_span(
+            self.mode(),
+            &identity.identifier,
+            &self.options.identifier_hash_salt,
+        );
+        let result = span.record_flags(self.identity_flags(&identity, options));
+        telemetry::record_evaluation("identity", self.mode(), result.is_ok());
+        result.map(|flags| identity.apply_overrides(flags))
+    }
+
+    fn identity_flags(
+        &self,
+        identity: &IdentityContext,
+        options: &RequestOptions,
+    ) -> Result<Flags, error::Error> {
+        // Evaluated outside of the lock, so that identities are evaluated concurrently
         let data = self.datastore.lock().unwrap();
-        let traits = traits.unwrap_or(vec![]);
-        if data.evaluation_context.is_some() {
-            let eval_context = data.evaluation_context.as_ref().unwrap();
-            let engine_traits: Vec<Trait> = traits.into_iter().map(|t| t.into()).collect();
-            return self.get_identity_flags_from_document(eval_context, identifier, engine_traits);
+        let eval_context = data.evaluation_context.clone();
+        let identity_overrides = Arc::clone(&data.identity_overrides);
+        drop(data);
+        if self.use_local_evaluation(options, eval_context.is_some())? {
+            return self
+                .get_identity_flags_from_document(
+                    &eval_context.unwrap(),
+                    &identity_overrides,
+                    &identity.identifier,
+                    identity.engine_traits(),
+                )
+                .map(|flags| self.apply_overrides(flags));
         }
-        return self.default_handler_if_err(self.get_identity_flags_from_api(
-            identifier,
-            traits,
-            transient.unwrap_or(false),
-        ));
+        self.default_handler_if_err(self.get_identity_flags_from_api(identity, options))
+            .map(|flags| self.apply_overrides(flags))
     }
-    // Returns a list of segments that the given identity is part of
-    pub fn get_identity_segments(
+    // Returns the flags for each of the given identities, in the same order. In local
+    // evaluation mode all identities are evaluated against a single snapshot of the
+    // environment. Otherwise, each identity is evaluated by its own request to the
+    // identities endpoint, as the bulk identities endpoint only persists identities
+    // and does not return flags. Either way, identities are split across
+    // `bulk_evaluation_parallelism` threads.
+    // An error for one identity fails the call, unless a default flag handler is set.
+    pub fn get_identities_flags(
         &self,
-        identifier: &str,
-        traits: Option<Vec<Trait>>,
-    ) -> Result<Vec<Segment>, error::Error> {
+        identities: &[(&str, Vec<SDKTrait>)],
+    ) -> Result<Vec<Flags>, error::Error> {
+        let identities: Vec<IdentityContext> = identities
+            .iter()
+            .map(|(identifier, traits)| IdentityContext::from((*identifier, traits.clone())))
+            .collect();
+        self.get_identities_flags_for(&identities)
+    }
+
+    // Same as `get_identities_flags`, for identities given as `IdentityContext`s
+    pub fn get_identities_flags_for(
+        &self,
+        identities: &[IdentityContext],
+    ) -> Result<Vec<Flags>, error::Error> {
+        self.get_identities_flags_with(identities, &RequestOptions::default())
+    }
+
+    fn get_identities_flags_with(
+        &self,
+        identities: &[IdentityContext],
+        options: &RequestOptions,
+    ) -> Result<Vec<Flags>, error::Error> {
+        let identities_flags = self.get_identities_flags_without_overrides(identities, options)?;
+        let data = self.datastore.lock().unwrap();
+        Ok(identities_flags
+            .into_iter()
+            .zip(identities)
+            .map(|(flags, identity)| identity.apply_overrides(data.overrides.apply(flags)))
+            .collect())
+    }
+
+    fn get_identities_flags_without_overrides(
+        &self,
+        identities: &[IdentityContext],
+        options: &RequestOptions,
+    ) -> Result<Vec<Flags>, error::Error> {
+        let data = self.datastore.lock().unwrap();
+        let eval_context = data.evaluation_context.clone();
+        let identity_overrides = Arc::clone(&data.identity_overrides);
+        drop(data);
+        if self.use_local_evaluation(options, eval_context.is_some())? {
+            return Ok(self.get_identities_flags_from_document(
+                &eval_context.unwrap(),
+                &identity_overrides,
+                identities,
+            ));
+        }
+        in_parallel(
+            identities,
+            self.options.bulk_evaluation_parallelism,
+            |chunk| {
+                chunk
+                    .iter()
+                    .map(|identity| {
+                        self.default_handler_if_err(
+                            self.get_identity_flags_from_api(identity, options),
+                        )
+                    })
+                    .collect()
+            },
+        )
+        .into_iter()
+        .collect()
+    }
+
+    // Returns the identifiers of the identities overriding the given feature, sorted.
+    // Overrides are indexed when the environment is loaded, so this is cheap.
+    pub fn get_identities_with_overrides(
+        &self,
+        feature_name: &str,
+    ) -> Result<Vec<String>, error::Error> {
         let data = self.datastore.lock().unwrap();
         if data.evaluation_context.is_none() {
             return Err(error::Error::new(
                 error::ErrorKind::FlagsmithClientError,
-                "Local evaluation required to obtain identity segments.".to_string(),
+                "Local evaluation required to list identity overrides.".to_string(),
             ));
         }
-        let eval_context = data.evaluation_context.as_ref().unwrap();
-        let traits = traits.unwrap_or(vec![]);
+        Ok(data.identity_overrides.identifiers_overriding(feature_name))
+    }
 
-        let context_with_identity = add_identity_to_context(eval_context, identifier, &traits);
+    // Returns a list of segments that the given identity is part of, along with
+    // their rules and segment overrides
+    pub fn get_identity_segments(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<Trait>>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        self.get_identity_segments_for((identifier, traits.unwrap_or_default()))
+    }
+
+    pub fn get_identity_segments_for(
+        &self,
+        identity: impl Into<IdentityContext>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        self.get_identity_segments_from_document(&identity.into(), false)
+    }
+
+    // Same as `get_identity_segments`, but also returns the identity overrides of the
+    // given identity, if any, as a segment named `identity_overrides`
+    pub fn get_identity_segments_with_overrides(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<Trait>>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        self.get_identity_segments_with_overrides_for((identifier, traits.unwrap_or_default()))
+    }
+
+    pub fn get_identity_segments_with_overrides_for(
+        &self,
+        identity: impl Into<IdentityContext>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        self.get_identity_segments_from_document(&identity.into(), true)
+    }
+
+    // Returns a trace of the local evaluation for the given identity: every segment
+    // with the outcome of each of its rules and conditions, and, for each flag, the
+    // segment override chosen and the multivariate hash percentage.
+    pub fn explain_identity(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<SDKTrait>>,
+    ) -> Result<IdentityExplanation, error::Error> {
+        self.explain_identity_for((identifier, traits.unwrap_or_default()))
+    }
+
+    pub fn explain_identity_for(
+        &self,
+        identity: impl Into<IdentityContext>,
+    ) -> Result<IdentityExplanation, error::Error> {
+        let identity = identity.into();
+        let data = self.datastore.lock().unwrap();
+        let eval_context = data.evaluation_context.clone();
+        let identity_overrides = Arc::clone(&data.identity_overrides);
+        drop(data);
+        let eval_context = eval_context.ok_or(error::Error::new(
+            error::ErrorKind::FlagsmithClientError,
+            "Local evaluation required to explain identity evaluation.".to_string(),
+        ))?;
+        let context_with_identity = identity_overrides.identity_context(
+            &eval_context,
+            &identity.identifier,
+            &identity.engine_traits(),
+        );
+        Ok(explain::explain_evaluation(&context_with_identity))
+    }
+
+    fn get_identity_segments_from_document(
+        &self,
+        identity: &IdentityContext,
+        include_identity_overrides: bool,
+    ) -> Result<Vec<Segment>, error::Error> {
+        let data = self.datastore.lock().unwrap();
+        let eval_context = data.evaluation_context.clone();
+        let environment = data.environment.clone();
+        let identity_overrides = Arc::clone(&data.identity_overrides);
+        drop(data);
+        let (eval_context, environment) = match (eval_context, environment) {
+            (Some(eval_context), Some(environment)) => (eval_context, environment),
+            _ => {
+                return Err(error::Error::new(
+                    error::ErrorKind::FlagsmithClientError,
+                    "Local evaluation required to obtain identity segments.".to_string(),
+                ))
+            }
+        };
+        let context_with_identity = identity_overrides.identity_context(
+            &eval_context,
+            &identity.identifier,
+            &identity.engine_traits(),
+        );
 
         let result = get_evaluation_result(&context_with_identity);
 
         let segments: Vec<Segment> = result
             .segments
             .iter()
-            .filter(|seg_result| seg_result.metadata.source == SegmentSource::Api)
-            .map(|seg_result| Segment {
-                id: seg_result.metadata.segment_id.unwrap_or(0) as u32,
-                name: seg_result.name.clone(),
-                rules: vec![],
-                feature_states: vec![],
+            .filter_map(|seg_result| match seg_result.metadata.source {
+                SegmentSource::Api => environment
+                    .project
+                    .segments
+                    .iter()
+                    .find(|segment| Some(segment.id as i32) == seg_result.metadata.segment_id)
+                    .cloned(),
+                SegmentSource::IdentityOverride if include_identity_overrides => Some(
+                    identity_overrides_segment(&environment, &identity.identifier),
+                ),
+                SegmentSource::IdentityOverride => None,
             })
             .collect();
 
-        return Ok(segments);
+        Ok(segments)
     }
 
     fn default_handler_if_err(
@@ -287,6 +884,7 @@ impl Flagsmith {
             Ok(result) => Ok(result),
             Err(e) => {
                 if self.options.default_flag_handler.is_some() {
+                    telemetry::record_default_flag_fallback("api_error");
                     return Ok(Flags::from_api_flags(
                         &vec![],
                         self.analytics_processor.clone(),
@@ -299,35 +897,25 @@ impl Flagsmith {
             }
         }
     }
-    fn get_environment_flags_from_document(
-        &self,
-        eval_context: &EngineEvaluationContext,
-    ) -> models::Flags {
-        // Clear segments and identity for environment evaluation
-        let environment_eval_ctx = EngineEvaluationContext {
-            environment: eval_context.environment.clone(),
-            features: eval_context.features.clone(),
-            segments: HashMap::new(),
-            identity: None,
-        };
-        let result = get_evaluation_result(&environment_eval_ctx);
-        return models::Flags::from_evaluation_result(
-            &result,
-            self.analytics_processor.clone(),
-            self.options.default_flag_handler,
-        );
-    }
     pub fn update_environment(&mut self) -> Result<(), error::Error> {
-        return update_environment(&self.client, &self.datastore, &self.environment_url);
+        update_environment(
+            &self.client,
+            &self.datastore,
+            &self.environment_url,
+            &self.analytics_processor,
+            self.options.default_flag_handler,
+        )
     }
 
     fn get_identity_flags_from_document(
         &self,
         eval_context: &EngineEvaluationContext,
+        identity_overrides: &IdentityOverrideIndex,
         identifier: &str,
         traits: Vec<Trait>,
     ) -> Result<Flags, error::Error> {
-        let context_with_identity = add_identity_to_context(eval_context, identifier, &traits);
+        let context_with_identity =
+            identity_overrides.identity_context(eval_context, identifier, &traits);
 
         let result = get_evaluation_result(&context_with_identity);
 
@@ -339,20 +927,65 @@ impl Flagsmith {
         return Ok(flags);
     }
 
+    fn get_identities_flags_from_document(
+        &self,
+        eval_context: &EngineEvaluationContext,
+        identity_overrides: &IdentityOverrideIndex,
+        identities: &[IdentityContext],
+    ) -> Vec<Flags> {
+        in_parallel(
+            identities,
+            self.options.bulk_evaluation_parallelism,
+            |chunk| self.evaluate_identities(eval_context, identity_overrides, chunk),
+        )
+    }
+
+    // Evaluates the identities one after the other, swapping the identity on a
+    // single copy of the context rather than cloning the context for each of them
+    fn evaluate_identities(
+        &self,
+        eval_context: &EngineEvaluationContext,
+        identity_overrides: &IdentityOverrideIndex,
+        identities: &[IdentityContext],
+    ) -> Vec<Flags> {
+        let mut context = eval_context.clone();
+        identities
+            .iter()
+            .map(|identity| {
+                let traits = identity
+                    .traits
+                    .iter()
+                    .map(|t| (t.trait_key.clone(), t.trait_value.clone()))
+                    .collect();
+                identity_overrides.set_identity(&mut context, &identity.identifier, traits);
+                let result = get_evaluation_result(&context);
+                Flags::from_evaluation_result(
+                    &result,
+                    self.analytics_processor.clone(),
+                    self.options.default_flag_handler,
+                )
+            })
+            .collect()
+    }
+
     fn get_identity_flags_from_api(
         &self,
-        identifier: &str,
-        traits: Vec<SDKTrait>,
-        transient: bool,
+        identity: &IdentityContext,
+        options: &RequestOptions,
     ) -> Result<Flags, error::Error> {
-        let method = reqwest::Method::POST;
+        let method = Method::POST;
 
-        let json = json!({"identifier":identifier, "traits": traits, "transient": transient});
+        let json = json!({
+            "identifier": identity.identifier,
+            "traits": identity.traits,
+            "transient": identity.transient,
+        });
         let response = get_json_response(
             &self.client,
             method,
             self.identities_url.clone(),
             Some(json.to_string()),
+            options,
         )?;
         // Cast to array of values
         let api_flags = response["flags"].as_array().ok_or(error::Error::new(
@@ -371,80 +1004,347 @@ impl Flagsmith {
         ))?;
         return Ok(flags);
     }
-    fn get_environment_flags_from_api(&self) -> Result<Flags, error::Error> {
-        let method = reqwest::Method::GET;
-        let api_flags = get_json_response(
+    fn get_environment_flags_from_api(
+        &self,
+        options: &RequestOptions,
+    ) -> Result<Flags, error::Error> {
+        get_environment_flags_from_api(
             &self.client,
-            method,
-            self.environment_flags_url.clone(),
-            None,
-        )?;
-        // Cast to array of values
-        let api_flags = api_flags.as_array().ok_or(error::Error::new(
-            error::ErrorKind::FlagsmithAPIError,
-            "Unable to get valid response from Flagsmith API.".to_string(),
-        ))?;
-
-        let flags = Flags::from_api_flags(
-            api_flags,
-            self.analytics_processor.clone(),
+            &self.environment_flags_url,
+            &self.analytics_processor,
             self.options.default_flag_handler,
+            options,
         )
+    }
+}
+
+fn get_environment_flags_from_api(
+    client: &ApiClient,
+    environment_flags_url: &str,
+    analytics_processor: &Option<AnalyticsProcessor>,
+    default_flag_handler: Option<fn(&str) -> Flag>,
+    options: &RequestOptions,
+) -> Result<Flags, error::Error> {
+    let method = Method::GET;
+    let api_flags = get_json_response(
+        client,
+        method,
+        environment_flags_url.to_string(),
+        None,
+        options,
+    )?;
+    // Cast to array of values
+    let api_flags = api_flags.as_array().ok_or(error::Error::new(
+        error::ErrorKind::FlagsmithAPIError,
+        "Unable to get valid response from Flagsmith API.".to_string(),
+    ))?;
+
+    let flags = Flags::from_api_flags(api_flags, analytics_processor.clone(), default_flag_handler)
         .ok_or(error::Error::new(
             error::ErrorKind::FlagsmithAPIError,
             "Unable to get valid response from Flagsmith API.".to_string(),
         ))?;
-        return Ok(flags);
+    Ok(flags)
+}
+
+// Identity overrides are evaluated as a segment matching all of the identifiers
+// sharing the same overrides; only expose the identifier that was asked for.
+fn identity_overrides_segment(environment: &Environment, identifier: &str) -> Segment {
+    let feature_states = environment
+        .identity_overrides
+        .iter()
+        .find(|identity| identity.identifier == identifier)
+        .map(|identity| identity.identity_features.clone())
+        .unwrap_or_default();
+    Segment {
+        id: 0,
+        name: IDENTITY_OVERRIDES_SEGMENT_NAME.to_string(),
+        rules: vec![SegmentRule {
+            segment_rule_type: ALL_RULE.to_string(),
+            rules: vec![],
+            conditions: vec![SegmentCondition {
+                operator: IN.to_string(),
+                value: Some(identifier.to_string()),
+                property: Some("$.identity.identifier".to_string()),
+            }],
+        }],
+        feature_states,
     }
 }
 
+// Returns the environment along with the document it was built from, which holds
+// the fields the engine model drops, such as `updated_at`
 fn get_environment_from_api(
-    client: &reqwest::blocking::Client,
+    client: &ApiClient,
     environment_url: String,
-) -> Result<Environment, error::Error> {
-    let method = reqwest::Method::GET;
-    let json_document = get_json_response(client, method, environment_url, None)?;
-    let environment = build_environment_struct(json_document);
-    return Ok(environment);
+) -> Result<(Environment, serde_json::Value), error::Error> {
+    let method = Method::GET;
+    let json_document = get_json_response(
+        client,
+        method,
+        environment_url,
+        None,
+        &RequestOptions::default(),
+    )?;
+    let environment = build_environment_struct(json_document.clone());
+    Ok((environment, json_document))
+}
+
+fn get_environment_flags_from_document(
+    eval_context: &EngineEvaluationContext,
+    analytics_processor: &Option<AnalyticsProcessor>,
+    default_flag_handler: Option<fn(&str) -> Flag>,
+) -> Flags {
+    // Clear segments and identity for environment evaluation
+    let environment_eval_ctx = EngineEvaluationContext {
+        environment: eval_context.environment.clone(),
+        features: eval_context.features.clone(),
+        segments: HashMap::new(),
+        identity: None,
+    };
+    let result = get_evaluation_result(&environment_eval_ctx);
+    Flags::from_evaluation_result(&result, analytics_processor.clone(), default_flag_handler)
 }
 
 fn update_environment(
-    client: &reqwest::blocking::Client,
+    client: &ApiClient,
+    datastore: &Arc<Mutex<DataStore>>,
+    environment_url: &str,
+    analytics_processor: &Option<AnalyticsProcessor>,
+    default_flag_handler: Option<fn(&str) -> Flag>,
+) -> Result<(), error::Error> {
+    let span = telemetry::update_environment_span();
+    let result = get_environment_from_api(client, environment_url.to_string());
+    let result = match result {
+        Ok((environment, environment_document)) => {
+            let feature_count = environment.feature_states.len();
+            set_environment(
+                datastore,
+                environment,
+                Some(environment_document),
+                analytics_processor,
+                default_flag_handler,
+            );
+            Ok(feature_count)
+        }
+        Err(e) => {
+            datastore.lock().unwrap().refresh_status.record_failure(&e);
+            Err(e)
+        }
+    };
+    span.record_update(&result);
+    result.map(|_| ())
+}
+
+// Builds the evaluation context and the environment flags outside of the lock,
+// then swaps them in together
+fn set_environment(
+    datastore: &Arc<Mutex<DataStore>>,
+    environment: Environment,
+    environment_document: Option<serde_json::Value>,
+    analytics_processor: &Option<AnalyticsProcessor>,
+    default_flag_handler: Option<fn(&str) -> Flag>,
+) {
+    // `updated_at` is only known from the document as served, the model drops it
+    let updated_at = environment_document
+        .as_ref()
+        .and_then(status::parse_updated_at);
+    let environment_document = environment_document.unwrap_or_else(|| json!(environment));
+    let mut eval_context = environment_to_context(environment.clone());
+    let identity_overrides = IdentityOverrideIndex::extract(&mut eval_context);
+    let environment_flags = get_environment_flags_from_document(
+        &eval_context,
+        analytics_processor,
+        default_flag_handler,
+    );
+
+    let mut data = datastore.lock().unwrap();
+    data.evaluation_context = Some(Arc::new(eval_context));
+    data.identity_overrides = Arc::new(identity_overrides);
+    data.environment = Some(Arc::new(environment));
+    data.environment_document = Some(Arc::new(environment_document));
+    data.refresh_status.record_success(updated_at);
+    let (listeners, previous) = data.replace_environment_flags(environment_flags.clone());
+    drop(data);
+
+    listeners.notify(previous.as_ref(), &environment_flags);
+}
+
+// Fetches environment flags before taking the lock so that readers keep being
+// served the cached flags while the request is in flight. On error the cache is
+// left untouched.
+fn update_environment_flags(
+    client: &ApiClient,
     datastore: &Arc<Mutex<DataStore>>,
-    environment_url: &String,
+    environment_flags_url: &str,
+    analytics_processor: &Option<AnalyticsProcessor>,
+    default_flag_handler: Option<fn(&str) -> Flag>,
 ) -> Result<(), error::Error> {
+    let result = get_environment_flags_from_api(
+        client,
+        environment_flags_url,
+        analytics_processor,
+        default_flag_handler,
+        &RequestOptions::default(),
+    );
     let mut data = datastore.lock().unwrap();
-    let environment = Some(get_environment_from_api(&client, environment_url.clone())?);
+    let flags = match result {
+        Ok(flags) => flags,
+        Err(e) => {
+            data.refresh_status.record_failure(&e);
+            return Err(e);
+        }
+    };
+    data.refresh_status.record_success(None);
+    let (listeners, previous) = data.replace_environment_flags(flags.clone());
+    drop(data);
+    listeners.notify(previous.as_ref(), &flags);
+    Ok(())
+}
 
-    let eval_context = environment_to_context(environment.as_ref().unwrap().clone());
-    data.evaluation_context = Some(eval_context);
+// Splits the items into `parallelism` chunks, each processed by its own scoped
+// thread, and returns the results in the order of the items
+fn in_parallel<T: Sync, R: Send>(
+    items: &[T],
+    parallelism: usize,
+    process: impl Fn(&[T]) -> Vec<R> + Sync,
+) -> Vec<R> {
+    let parallelism = parallelism.max(1);
+    if parallelism == 1 || items.len() < 2 {
+        return process(items);
+    }
+    let chunk_size = items.len().div_ceil(parallelism);
+    let process = &process;
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || process(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
 
-    data.environment = environment;
-    return Ok(());
+// Checks that the options can be used together
+fn validate_options(flagsmith_options: &FlagsmithOptions) -> Result<(), error::Error> {
+    let invalid = |msg: &str| {
+        Err(error::Error::new(
+            error::ErrorKind::FlagsmithClientError,
+            msg.to_string(),
+        ))
+    };
+    if flagsmith_options.offline_mode && flagsmith_options.offline_handler.is_none() {
+        return invalid("offline_handler must be set to use offline_mode");
+    }
+    if flagsmith_options.default_flag_handler.is_some()
+        && flagsmith_options.offline_handler.is_some()
+    {
+        return invalid("default_flag_handler cannot be used with offline_handler");
+    }
+    if flagsmith_options.enable_local_evaluation && flagsmith_options.offline_handler.is_some() {
+        return invalid("offline_handler cannot be used with local evaluation");
+    }
+    if flagsmith_options.enable_environment_flags_cache && flagsmith_options.enable_local_evaluation
+    {
+        return invalid("enable_environment_flags_cache cannot be used with local evaluation");
+    }
+    if flagsmith_options.enable_environment_flags_cache
+        && flagsmith_options.offline_handler.is_some()
+    {
+        return invalid("enable_environment_flags_cache cannot be used with offline_handler");
+    }
+    if flagsmith_options.strict_local_evaluation && !flagsmith_options.enable_local_evaluation {
+        return invalid("strict_local_evaluation requires enable_local_evaluation");
+    }
+    Ok(())
+}
+
+// Checks that the environment key can be used with the options
+fn validate_environment_key(
+    flagsmith_options: &FlagsmithOptions,
+    environment_key: &str,
+) -> Result<(), error::Error> {
+    if flagsmith_options.enable_local_evaluation && !environment_key.starts_with("ser.") {
+        return Err(error::Error::new(
+            error::ErrorKind::FlagsmithClientError,
+            "In order to use local evaluation, please use a server-side environment key (starts with 'ser.')".to_string(),
+        ));
+    }
+    if header::HeaderValue::from_str(environment_key).is_err() {
+        return Err(error::Error::new(
+            error::ErrorKind::FlagsmithClientError,
+            "The environment key is not a valid header value.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn build_transport(flagsmith_options: &FlagsmithOptions) -> Arc<dyn HttpTransport> {
+    let configures_default_transport = flagsmith_options.proxy.is_some()
+        || !flagsmith_options.root_certificates.is_empty()
+        || flagsmith_options.client_certificate.is_some()
+        || flagsmith_options.connect_timeout_seconds.is_some();
+    match &flagsmith_options.transport {
+        Some(_) if configures_default_transport => panic!(
+            "proxy, root_certificates, client_certificate and connect_timeout_seconds cannot be used with transport"
+        ),
+        Some(transport) => Arc::clone(transport),
+        #[cfg(feature = "reqwest")]
+        None => Arc::new(
+            transport::ReqwestTransport::from_options(flagsmith_options)
+                .unwrap_or_else(|e| panic!("Failed to build the HTTP client: {}", e)),
+        ),
+        #[cfg(not(feature = "reqwest"))]
+        None => panic!("transport must be set when the reqwest feature is disabled"),
+    }
+}
+
+fn spawn_polling_thread<F>(rx: Receiver<u32>, refresh_interval_mills: u64, refresh: F)
+where
+    F: Fn() + Send + 'static,
+{
+    thread::spawn(move || loop {
+        match rx.try_recv() {
+            Ok(_) | Err(TryRecvError::Disconnected) => {
+                debug!("shutting down polling manager");
+                break;
+            }
+            Err(TryRecvError::Empty) => {}
+        }
+        thread::sleep(Duration::from_millis(refresh_interval_mills));
+        refresh();
+    });
 }
 
 fn get_json_response(
-    client: &reqwest::blocking::Client,
-    method: reqwest::Method,
+    client: &ApiClient,
+    method: Method,
     url: String,
     body: Option<String>,
+    options: &RequestOptions,
 ) -> Result<serde_json::Value, error::Error> {
-    let mut request = client.request(method, url);
-    if body.is_some() {
-        request = request.body(body.unwrap());
-    };
-    let response = request.send()?;
-    if response.status().is_success() {
-        return Ok(response.json()?);
+    let span = telemetry::http_span(&method, &url);
+    let endpoint = telemetry::endpoint_label(&url).to_string();
+    let started = Instant::now();
+    let response = client.send(method, url, body.map(String::into_bytes), options);
+    let status_code = response.as_ref().ok().map(|response| response.status);
+    span.record_http(status_code, started.elapsed());
+    telemetry::record_http_request(&endpoint, status_code, started.elapsed());
+    let response = response?;
+    if response.is_success() {
+        Ok(serde_json::from_slice(&response.body)?)
     } else {
-        return Err(error::Error::new(
+        Err(error::Error::new(
             error::ErrorKind::FlagsmithAPIError,
-            response.text()?,
-        ));
+            String::from_utf8_lossy(&response.body).to_string(),
+        ))
     }
 }
 
-#[cfg(test)]
+// The tests build clients with the default transport
+#[cfg(all(test, feature = "reqwest"))]
 mod tests {
     use super::*;
     use httpmock::prelude::*;
@@ -674,6 +1574,81 @@ mod tests {
         );
     }
 
+    #[test]
+    fn update_environment_precomputes_environment_flags() {
+        // Given
+        let environment_key = "ser.test_environment_key";
+        let response_body: serde_json::Value = serde_json::from_str(ENVIRONMENT_JSON).unwrap();
+
+        let mock_server = MockServer::start();
+        mock_server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v1/environment-document/")
+                .header("X-Environment-Key", environment_key);
+            then.status(200).json_body(response_body);
+        });
+
+        let flagsmith_options = FlagsmithOptions {
+            api_url: mock_server.url("/api/v1/"),
+            ..Default::default()
+        };
+        let mut flagsmith = Flagsmith::new(environment_key.to_string(), flagsmith_options);
+
+        // When
+        flagsmith.update_environment().unwrap();
+
+        // Then
+        let data = flagsmith.datastore.lock().unwrap();
+        let environment_flags = data.environment_flags.as_ref().unwrap();
+        assert_eq!(
+            environment_flags
+                .get_feature_value_as_string("some_feature")
+                .unwrap(),
+            "some-value"
+        );
+    }
+
+    #[test]
+    fn explain_identity_reports_multivariate_hash_percentage() {
+        // Given
+        let environment_key = "ser.test_environment_key";
+        let response_body: serde_json::Value = serde_json::from_str(ENVIRONMENT_JSON).unwrap();
+
+        let mock_server = MockServer::start();
+        mock_server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v1/environment-document/")
+                .header("X-Environment-Key", environment_key);
+            then.status(200).json_body(response_body);
+        });
+
+        let flagsmith_options = FlagsmithOptions {
+            api_url: mock_server.url("/api/v1/"),
+            ..Default::default()
+        };
+        let mut flagsmith = Flagsmith::new(environment_key.to_string(), flagsmith_options);
+        flagsmith.update_environment().unwrap();
+
+        // When
+        let explanation = flagsmith.explain_identity("some-identity", None).unwrap();
+
+        // Then
+        let mv_flag = explanation
+            .flags
+            .iter()
+            .find(|flag| flag.feature_name == "test_mv")
+            .unwrap();
+        assert!(mv_flag.hash_percentage.is_some());
+        assert_eq!(mv_flag.value.value, "8888");
+        assert_eq!(mv_flag.reason, "SPLIT; weight=100");
+        let standard_flag = explanation
+            .flags
+            .iter()
+            .find(|flag| flag.feature_name == "some_feature")
+            .unwrap();
+        assert!(standard_flag.hash_percentage.is_none());
+    }
+
     #[test]
     fn test_user_agent_header_is_set() {
         // Given
@@ -1,11 +1,13 @@
 use crate::flagsmith::analytics::AnalyticsProcessor;
+use crate::flagsmith::telemetry;
 use core::f64;
 use flagsmith_flag_engine::engine_eval::EvaluationResult;
 use flagsmith_flag_engine::features::FeatureState;
 use flagsmith_flag_engine::identities::Trait;
 use flagsmith_flag_engine::types::{FlagsmithValue, FlagsmithValueType};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use crate::error;
 
@@ -42,6 +44,16 @@ impl Flag {
         };
         Some(flag)
     }
+
+    // Inverse of `from_api_flag`: the flag as returned by the flags API endpoints
+    pub fn to_api_flag(&self) -> serde_json::Value {
+        serde_json::json!({
+            "feature": {"id": self.feature_id, "name": self.feature_name},
+            "enabled": self.enabled,
+            "feature_state_value": self.value,
+        })
+    }
+
     pub fn value_as_string(&self) -> Option<String> {
         match self.value.value_type {
             FlagsmithValueType::String => Some(self.value.value.clone()),
@@ -72,11 +84,17 @@ impl Flag {
     }
 }
 
+// Cloning `Flags` is cheap: the flags themselves are shared
 #[derive(Clone)]
 pub struct Flags {
-    flags: HashMap<String, Flag>,
+    flags: Arc<HashMap<String, Flag>>,
     analytics_processor: Option<AnalyticsProcessor>,
     default_flag_handler: Option<fn(&str) -> Flag>,
+    // Names of the flags forced by a local override, which are not reported to analytics
+    overridden: Arc<HashSet<String>>,
+    // Receives the name of every feature read through `get_flag`, set or not, for
+    // `FakeFlagsmith` to record reads
+    reads_tx: Option<flume::Sender<String>>,
 }
 
 impl Flags {
@@ -94,9 +112,11 @@ impl Flags {
             );
         }
         return Flags {
-            flags,
+            flags: Arc::new(flags),
             analytics_processor,
             default_flag_handler,
+            overridden: Arc::default(),
+            reads_tx: None,
         };
     }
     pub fn from_api_flags(
@@ -110,9 +130,11 @@ impl Flags {
             flags.insert(flag.feature_name.clone(), flag);
         }
         return Some(Flags {
-            flags,
+            flags: Arc::new(flags),
             analytics_processor,
             default_flag_handler,
+            overridden: Arc::default(),
+            reads_tx: None,
         });
     }
 
@@ -133,15 +155,75 @@ impl Flags {
             flags.insert(feature_name.clone(), flag);
         }
         return Flags {
-            flags,
+            flags: Arc::new(flags),
             analytics_processor,
             default_flag_handler,
+            overridden: Arc::default(),
+            reads_tx: None,
         };
     }
 
+    pub(crate) fn from_map(
+        flags: HashMap<String, Flag>,
+        analytics_processor: Option<AnalyticsProcessor>,
+        default_flag_handler: Option<fn(&str) -> Flag>,
+    ) -> Flags {
+        Flags {
+            flags: Arc::new(flags),
+            analytics_processor,
+            default_flag_handler,
+            overridden: Arc::default(),
+            reads_tx: None,
+        }
+    }
+
+    #[cfg(feature = "testing")]
+    pub(crate) fn with_reads_tx(mut self, reads_tx: flume::Sender<String>) -> Flags {
+        self.reads_tx = Some(reads_tx);
+        self
+    }
+
+    pub(crate) fn as_map(&self) -> &HashMap<String, Flag> {
+        &self.flags
+    }
+
+    // Returns a copy of these flags with the given flags replaced or added, keeping
+    // the feature ids of the replaced flags
+    pub(crate) fn with_overrides(&self, overrides: &HashMap<String, Flag>) -> Flags {
+        let mut flags = (*self.flags).clone();
+        for (feature_name, override_flag) in overrides {
+            let feature_id = flags
+                .get(feature_name)
+                .map_or(override_flag.feature_id, |flag| flag.feature_id);
+            flags.insert(
+                feature_name.clone(),
+                Flag {
+                    feature_id,
+                    ..override_flag.clone()
+                },
+            );
+        }
+        let mut overridden = (*self.overridden).clone();
+        overridden.extend(overrides.keys().cloned());
+        Flags {
+            overridden: Arc::new(overridden),
+            reads_tx: self.reads_tx.clone(),
+            ..Flags::from_map(
+                flags,
+                self.analytics_processor.clone(),
+                self.default_flag_handler,
+            )
+        }
+    }
+
+    // Whether the flag of the given feature is forced by a local override
+    pub fn is_overridden(&self, feature_name: &str) -> bool {
+        self.overridden.contains(feature_name)
+    }
+
     // Returns a vector of all `Flag` structs
     pub fn all_flags(&self) -> Vec<Flag> {
-        return self.flags.clone().into_values().collect();
+        self.flags.values().cloned().collect()
     }
 
     // Check whether a given feature is enabled.
@@ -159,9 +241,16 @@ impl Flags {
 
     // Returns a specific `Flag` given the feature name
     pub fn get_flag(&self, feature_name: &str) -> Result<Flag, error::Error> {
+        if let Some(reads_tx) = &self.reads_tx {
+            let _ = reads_tx.send(feature_name.to_string());
+        }
         match self.flags.get(&feature_name.to_string()) {
             Some(flag) => {
-                if self.analytics_processor.is_some() && !flag.is_default {
+                telemetry::record_flag_evaluation(feature_name);
+                if self.analytics_processor.is_some()
+                    && !flag.is_default
+                    && !self.is_overridden(feature_name)
+                {
                     let _ = self
                         .analytics_processor
                         .as_ref()
@@ -172,7 +261,10 @@ impl Flags {
                 return Ok(flag.clone());
             }
             None => match self.default_flag_handler {
-                Some(handler) => Ok(handler(feature_name)),
+                Some(handler) => {
+                    telemetry::record_default_flag_fallback("missing_flag");
+                    Ok(handler(feature_name))
+                }
                 None => Err(error::Error::new(
                     error::ErrorKind::FlagsmithAPIError,
                     "API returned invalid response".to_string(),
@@ -211,6 +303,126 @@ impl SDKTrait {
     }
 }
 
+impl SDKTrait {
+    // Trait with a value of any type implementing `IntoTraitValue`, e.g.
+    // `SDKTrait::from_value("age", 42)`. A `None` value deletes the trait.
+    pub fn from_value(trait_key: impl Into<String>, trait_value: impl IntoTraitValue) -> Self {
+        SDKTrait::new(trait_key.into(), trait_value.into_trait_value())
+    }
+
+    pub fn with_transient(mut self, transient: bool) -> Self {
+        self.transient = transient;
+        self
+    }
+
+    // Traits from a JSON object, e.g. `{"plan": "premium", "age": 42}`. Fails on
+    // arrays and objects, which are not valid trait values.
+    pub fn from_json(
+        traits: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Vec<Self>, error::Error> {
+        traits
+            .iter()
+            .map(|(trait_key, trait_value)| match trait_value {
+                serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+                    Err(error::Error::new(
+                        error::ErrorKind::FlagsmithClientError,
+                        format!("Invalid value for trait {}: {}", trait_key, trait_value),
+                    ))
+                }
+                _ => Ok(SDKTrait::new(
+                    trait_key.clone(),
+                    serde_json::from_value(trait_value.clone())?,
+                )),
+            })
+            .collect()
+    }
+}
+
+// `("plan", "premium").into()`
+impl<K: Into<String>, V: IntoTraitValue> From<(K, V)> for SDKTrait {
+    fn from((trait_key, trait_value): (K, V)) -> Self {
+        SDKTrait::from_value(trait_key, trait_value)
+    }
+}
+
+// Values traits can be set to. `From` cannot be implemented for `FlagsmithValue`,
+// which is defined in the engine crate.
+pub trait IntoTraitValue {
+    fn into_trait_value(self) -> FlagsmithValue;
+}
+
+impl IntoTraitValue for FlagsmithValue {
+    fn into_trait_value(self) -> FlagsmithValue {
+        self
+    }
+}
+
+impl IntoTraitValue for &str {
+    fn into_trait_value(self) -> FlagsmithValue {
+        self.to_string().into_trait_value()
+    }
+}
+
+impl IntoTraitValue for String {
+    fn into_trait_value(self) -> FlagsmithValue {
+        FlagsmithValue {
+            value: self,
+            value_type: FlagsmithValueType::String,
+        }
+    }
+}
+
+impl IntoTraitValue for bool {
+    fn into_trait_value(self) -> FlagsmithValue {
+        FlagsmithValue {
+            value: self.to_string(),
+            value_type: FlagsmithValueType::Bool,
+        }
+    }
+}
+
+macro_rules! impl_into_trait_value {
+    ($value_type:ident: $($t:ty),+) => {
+        $(
+            impl IntoTraitValue for $t {
+                fn into_trait_value(self) -> FlagsmithValue {
+                    FlagsmithValue {
+                        value: self.to_string(),
+                        value_type: FlagsmithValueType::$value_type,
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_into_trait_value!(Integer: i32, i64, u32);
+impl_into_trait_value!(Float: f32, f64);
+
+// `None` deletes the trait from the identity
+impl<T: IntoTraitValue> IntoTraitValue for Option<T> {
+    fn into_trait_value(self) -> FlagsmithValue {
+        match self {
+            Some(value) => value.into_trait_value(),
+            None => FlagsmithValue::default(),
+        }
+    }
+}
+
+// Builds a `Vec<SDKTrait>` from `key => value` pairs, with values of any type
+// implementing `IntoTraitValue`.
+// # Example
+// ```
+// use flagsmith::traits;
+// let traits = traits!("plan" => "premium", "age" => 42, "beta" => true, "legacy" => None::<bool>);
+// ```
+#[macro_export]
+macro_rules! traits {
+    ($($trait_key:expr => $trait_value:expr),* $(,)?) => {
+        vec![$($crate::flagsmith::models::SDKTrait::from_value($trait_key, $trait_value)),*]
+    };
+}
+
 impl From<SDKTrait> for Trait {
     fn from(t: SDKTrait) -> Self {
         Self {
@@ -220,6 +432,12 @@ impl From<SDKTrait> for Trait {
     }
 }
 
+impl From<Trait> for SDKTrait {
+    fn from(t: Trait) -> Self {
+        SDKTrait::new(t.trait_key, t.trait_value)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -244,6 +462,70 @@ mod tests {
             "enabled": false
         }"#;
 
+    #[test]
+    fn sdk_traits_can_be_created_from_typed_values() {
+        // When
+        let traits = crate::traits!(
+            "string" => "premium",
+            "owned_string" => "premium".to_string(),
+            "bool" => true,
+            "integer" => 42,
+            "float" => 10.1,
+            "deleted" => None::<i64>,
+        );
+
+        // Then
+        let value_types: Vec<&FlagsmithValueType> =
+            traits.iter().map(|t| &t.trait_value.value_type).collect();
+        assert_eq!(
+            value_types,
+            vec![
+                &FlagsmithValueType::String,
+                &FlagsmithValueType::String,
+                &FlagsmithValueType::Bool,
+                &FlagsmithValueType::Integer,
+                &FlagsmithValueType::Float,
+                &FlagsmithValueType::None,
+            ]
+        );
+        assert_eq!(traits[4].trait_value.value, "10.1");
+        assert_eq!(
+            serde_json::to_value(&traits[5]).unwrap()["trait_value"],
+            serde_json::Value::Null
+        );
+        let sdk_trait: SDKTrait = ("age", Some(42)).into();
+        assert_eq!(sdk_trait.trait_value.value, "42");
+        assert!(
+            SDKTrait::from_value("age", 42)
+                .with_transient(true)
+                .transient
+        );
+    }
+
+    #[test]
+    fn sdk_traits_can_be_created_from_json() {
+        // Given
+        let traits = serde_json::json!({"plan": "premium", "age": 42, "legacy": null});
+        let invalid_traits = serde_json::json!({"tags": ["a", "b"]});
+
+        // When
+        let mut traits = SDKTrait::from_json(traits.as_object().unwrap()).unwrap();
+        let err = SDKTrait::from_json(invalid_traits.as_object().unwrap())
+            .err()
+            .unwrap();
+
+        // Then
+        traits.sort_by(|a, b| a.trait_key.cmp(&b.trait_key));
+        assert_eq!(traits[0].trait_key, "age");
+        assert_eq!(
+            traits[0].trait_value.value_type,
+            FlagsmithValueType::Integer
+        );
+        assert_eq!(traits[1].trait_value.value_type, FlagsmithValueType::None);
+        assert_eq!(traits[2].trait_value.value, "premium");
+        assert_eq!(err.kind, error::ErrorKind::FlagsmithClientError);
+    }
+
     #[test]
     fn can_create_flag_from_feature_state() {
         // Given
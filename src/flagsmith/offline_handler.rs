@@ -3,10 +3,17 @@ use std::fs;
 
 pub trait OfflineHandler {
     fn get_environment(&self) -> Environment;
+
+    // Environment document as read, with the fields the engine model drops, if the
+    // handler has it
+    fn get_environment_document(&self) -> Option<serde_json::Value> {
+        None
+    }
 }
 
 pub struct LocalFileHandler {
     environment: Environment,
+    environment_document: serde_json::Value,
 }
 
 impl LocalFileHandler {
@@ -15,10 +22,15 @@ impl LocalFileHandler {
         let environment_document = fs::read(environment_document_path)?;
 
         // Deserialize the JSON into EnvironmentModel
-        let environment: Environment = serde_json::from_slice(&environment_document)?;
+        let environment_document: serde_json::Value =
+            serde_json::from_slice(&environment_document)?;
+        let environment: Environment = serde_json::from_value(environment_document.clone())?;
 
         // Create and initialize the LocalFileHandler
-        let handler = LocalFileHandler { environment };
+        let handler = LocalFileHandler {
+            environment,
+            environment_document,
+        };
 
         Ok(handler)
     }
@@ -28,6 +40,27 @@ impl OfflineHandler for LocalFileHandler {
     fn get_environment(&self) -> Environment {
         self.environment.clone()
     }
+
+    fn get_environment_document(&self) -> Option<serde_json::Value> {
+        Some(self.environment_document.clone())
+    }
+}
+
+// Serves an environment held in memory, e.g. one made with `EnvironmentBuilder`
+pub struct InMemoryHandler {
+    environment: Environment,
+}
+
+impl InMemoryHandler {
+    pub fn new(environment: Environment) -> Self {
+        InMemoryHandler { environment }
+    }
+}
+
+impl OfflineHandler for InMemoryHandler {
+    fn get_environment(&self) -> Environment {
+        self.environment.clone()
+    }
 }
 
 #[cfg(test)]
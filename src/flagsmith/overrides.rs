@@ -0,0 +1,132 @@
+use super::models::{Flag, Flags};
+use flagsmith_flag_engine::types::FlagsmithValue;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+// Environment variable holding the path of a local overrides file, used when
+// `FlagsmithOptions.local_overrides_path` is not set
+pub const LOCAL_OVERRIDES_PATH_ENV_VAR: &str = "FLAGSMITH_LOCAL_OVERRIDES_PATH";
+
+// Entry of a local overrides file, e.g.
+// `{"my_feature": {"enabled": true, "value": "forced"}}`
+#[derive(Deserialize)]
+struct OverrideEntry {
+    #[serde(default = "enabled_by_default")]
+    enabled: bool,
+    #[serde(default)]
+    value: FlagsmithValue,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+// Flags forced locally, applied on top of the flags served by the client
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LocalOverrides {
+    flags: HashMap<String, Flag>,
+}
+
+impl LocalOverrides {
+    pub(crate) fn from_file(path: &str) -> Result<LocalOverrides, std::io::Error> {
+        let overrides_document = fs::read(path)?;
+        let entries: HashMap<String, OverrideEntry> = serde_json::from_slice(&overrides_document)?;
+
+        let mut overrides = LocalOverrides::default();
+        for (feature_name, entry) in entries {
+            overrides.set(&feature_name, entry.enabled, entry.value);
+        }
+        Ok(overrides)
+    }
+
+    pub(crate) fn set(&mut self, feature_name: &str, enabled: bool, value: FlagsmithValue) {
+        self.flags.insert(
+            feature_name.to_string(),
+            Flag {
+                enabled,
+                value,
+                is_default: false,
+                feature_id: 0,
+                feature_name: feature_name.to_string(),
+            },
+        );
+    }
+
+    pub(crate) fn remove(&mut self, feature_name: &str) {
+        self.flags.remove(feature_name);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.flags.clear();
+    }
+
+    pub(crate) fn apply(&self, flags: Flags) -> Flags {
+        if self.flags.is_empty() {
+            return flags;
+        }
+        flags.with_overrides(&self.flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flagsmith_flag_engine::types::FlagsmithValueType;
+
+    #[test]
+    fn apply_replaces_and_adds_flags() {
+        // Given
+        let flags = Flags::from_api_flags(
+            &vec![serde_json::json!({
+                "feature": {"id": 7, "name": "feature_1"},
+                "feature_state_value": "remote",
+                "enabled": false
+            })],
+            None,
+            None,
+        )
+        .unwrap();
+        let mut overrides = LocalOverrides::default();
+        let value = FlagsmithValue {
+            value: "local".to_string(),
+            value_type: FlagsmithValueType::String,
+        };
+        overrides.set("feature_1", true, value.clone());
+        overrides.set("feature_2", true, value);
+
+        // When
+        let flags = overrides.apply(flags);
+
+        // Then
+        let feature_1 = flags.get_flag("feature_1").unwrap();
+        assert!(feature_1.enabled);
+        assert!(flags.is_overridden("feature_1"));
+        assert_eq!(feature_1.feature_id, 7);
+        assert_eq!(feature_1.value_as_string().unwrap(), "local");
+        assert!(flags.is_overridden("feature_2"));
+    }
+
+    #[test]
+    fn from_file_reads_overrides() {
+        // Given
+        let path = std::env::temp_dir().join("flagsmith_local_overrides_test.json");
+        fs::write(
+            &path,
+            r#"{"feature_1": {"value": 10}, "feature_2": {"enabled": false}}"#,
+        )
+        .unwrap();
+
+        // When
+        let overrides = LocalOverrides::from_file(path.to_str().unwrap()).unwrap();
+
+        // Then
+        let feature_1 = &overrides.flags["feature_1"];
+        assert!(feature_1.enabled);
+        assert_eq!(feature_1.value_as_i64(), Some(10));
+        let feature_2 = &overrides.flags["feature_2"];
+        assert!(!feature_2.enabled);
+        assert_eq!(feature_2.value.value_type, FlagsmithValueType::None);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,264 @@
+use super::identity::IdentityContext;
+use super::models::{Flags, SDKTrait};
+use super::transport::HttpTransport;
+use super::{build_transport, validate_options, Flagsmith, FlagsmithOptions};
+use crate::error;
+use flagsmith_flag_engine::identities::Trait;
+use flagsmith_flag_engine::segments::Segment;
+use log::debug;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Refreshes are delayed by up to this fraction of the refresh interval, so that
+// environments added together do not keep hitting the API at the same time
+const REFRESH_JITTER_RATIO: f64 = 0.1;
+
+struct PooledEnvironment {
+    flagsmith: Arc<Flagsmith>,
+    next_refresh: Instant,
+}
+
+type Environments = Arc<RwLock<HashMap<String, PooledEnvironment>>>;
+
+// Serves several Flagsmith environments, e.g. one per tenant, through a single HTTP
+// transport and a single background thread refreshing the environments in turn.
+// Every environment uses the same options; `offline_handler` is not supported.
+// # Example
+// ```
+// use flagsmith::flagsmith::pool::FlagsmithPool;
+// use flagsmith::FlagsmithOptions;
+// let pool = FlagsmithPool::new(FlagsmithOptions {
+//     enable_local_evaluation: true,
+//     ..Default::default()
+// });
+// pool.add_environment("ser.tenant_1_key").unwrap();
+// pool.add_environment("ser.tenant_2_key").unwrap();
+// let flags = pool.get_identity_flags("ser.tenant_1_key", "user_1", None, None);
+// ```
+pub struct FlagsmithPool {
+    transport: Arc<dyn HttpTransport>,
+    options: Arc<FlagsmithOptions>,
+    environments: Environments,
+    // Wakes the scheduler up when an environment is added; dropping it shuts the
+    // scheduler down
+    scheduler_tx: SyncSender<()>,
+}
+
+impl FlagsmithPool {
+    pub fn new(flagsmith_options: FlagsmithOptions) -> Self {
+        if flagsmith_options.offline_handler.is_some() {
+            panic!("offline_handler cannot be used with FlagsmithPool")
+        }
+        if let Err(e) = validate_options(&flagsmith_options) {
+            panic!("{}", e.msg)
+        }
+        let transport = build_transport(&flagsmith_options);
+        let refresh_interval =
+            Duration::from_millis(flagsmith_options.environment_refresh_interval_mills);
+        let environments: Environments = Arc::new(RwLock::new(HashMap::new()));
+        let (tx, rx) = mpsc::sync_channel::<()>(1);
+        spawn_scheduler_thread(rx, Arc::clone(&environments), refresh_interval);
+
+        FlagsmithPool {
+            transport,
+            options: Arc::new(flagsmith_options),
+            environments,
+            scheduler_tx: tx,
+        }
+    }
+
+    // Adds an environment, loading it before returning as `Flagsmith::new` does.
+    // Does nothing if the environment is already in the pool, and returns an error
+    // if the environment key cannot be used with the options of the pool or the local
+    // overrides file cannot be read.
+    pub fn add_environment(&self, environment_key: &str) -> Result<(), error::Error> {
+        if self
+            .environments
+            .read()
+            .unwrap()
+            .contains_key(environment_key)
+        {
+            return Ok(());
+        }
+        // Built outside of the lock, so that an error cannot leave it poisoned
+        let (flagsmith, _) = Flagsmith::with_transport(
+            environment_key.to_string(),
+            Arc::clone(&self.options),
+            Arc::clone(&self.transport),
+        )?;
+        let flagsmith = Arc::new(flagsmith);
+        let flagsmith = {
+            let mut environments = self.environments.write().unwrap();
+            // Another call may have added the environment in the meantime
+            if environments.contains_key(environment_key) {
+                return Ok(());
+            }
+            let refresh_interval =
+                Duration::from_millis(self.options.environment_refresh_interval_mills);
+            environments.insert(
+                environment_key.to_string(),
+                PooledEnvironment {
+                    flagsmith: Arc::clone(&flagsmith),
+                    next_refresh: Instant::now() + with_jitter(refresh_interval),
+                },
+            );
+            flagsmith
+        };
+        // Loaded outside of the lock, so that the other environments keep being served
+        flagsmith.load_environment();
+        // A full channel means the scheduler is already due to wake up
+        let _ = self.scheduler_tx.try_send(());
+        Ok(())
+    }
+
+    // Stops refreshing the environment. Returns whether it was in the pool.
+    pub fn remove_environment(&self, environment_key: &str) -> bool {
+        self.environments
+            .write()
+            .unwrap()
+            .remove(environment_key)
+            .is_some()
+    }
+
+    pub fn environment_keys(&self) -> Vec<String> {
+        self.environments.read().unwrap().keys().cloned().collect()
+    }
+
+    // Client of the given environment, for the methods not routed by the pool
+    pub fn get(&self, environment_key: &str) -> Option<Arc<Flagsmith>> {
+        self.environments
+            .read()
+            .unwrap()
+            .get(environment_key)
+            .map(|environment| Arc::clone(&environment.flagsmith))
+    }
+
+    pub fn get_environment_flags(&self, environment_key: &str) -> Result<Flags, error::Error> {
+        self.route(environment_key)?.get_environment_flags()
+    }
+
+    pub fn get_identity_flags(
+        &self,
+        environment_key: &str,
+        identifier: &str,
+        traits: Option<Vec<SDKTrait>>,
+        transient: Option<bool>,
+    ) -> Result<Flags, error::Error> {
+        self.route(environment_key)?
+            .get_identity_flags(identifier, traits, transient)
+    }
+
+    pub fn get_identity_segments(
+        &self,
+        environment_key: &str,
+        identifier: &str,
+        traits: Option<Vec<Trait>>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        self.route(environment_key)?
+            .get_identity_segments(identifier, traits)
+    }
+
+    pub fn get_identity_flags_for(
+        &self,
+        environment_key: &str,
+        identity: impl Into<IdentityContext>,
+    ) -> Result<Flags, error::Error> {
+        self.route(environment_key)?
+            .get_identity_flags_for(identity)
+    }
+
+    pub fn get_identity_segments_for(
+        &self,
+        environment_key: &str,
+        identity: impl Into<IdentityContext>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        self.route(environment_key)?
+            .get_identity_segments_for(identity)
+    }
+
+    fn route(&self, environment_key: &str) -> Result<Arc<Flagsmith>, error::Error> {
+        self.get(environment_key).ok_or(error::Error::new(
+            error::ErrorKind::FlagsmithClientError,
+            "Environment not found in the pool.".to_string(),
+        ))
+    }
+}
+
+// Refreshes the environments that are due one after the other, then sleeps until
+// the next one is, or until an environment is added. A slow environment therefore delays the refreshes of the others.
+fn spawn_scheduler_thread(
+    rx: Receiver<()>,
+    environments: Environments,
+    refresh_interval: Duration,
+) {
+    thread::spawn(move || loop {
+        let now = Instant::now();
+        let wait = environments
+            .read()
+            .unwrap()
+            .values()
+            .map(|environment| environment.next_refresh.saturating_duration_since(now))
+            .min();
+        let woken = match wait {
+            Some(wait) => rx.recv_timeout(wait),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        if let Err(RecvTimeoutError::Disconnected) = woken {
+            debug!("shutting down pool scheduler");
+            break;
+        }
+
+        let now = Instant::now();
+        let due: Vec<(String, Arc<Flagsmith>)> = environments
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, environment)| environment.next_refresh <= now)
+            .map(|(key, environment)| (key.clone(), Arc::clone(&environment.flagsmith)))
+            .collect();
+        for (environment_key, flagsmith) in due {
+            if let Err(e) = flagsmith.refresh() {
+                log::warn!(
+                    "Failed to refresh pooled environment: {}. Will retry on next interval.",
+                    e
+                );
+            }
+            // The environment may have been removed during the refresh
+            if let Some(environment) = environments.write().unwrap().get_mut(&environment_key) {
+                environment.next_refresh = Instant::now() + with_jitter(refresh_interval);
+            }
+        }
+    });
+}
+
+fn with_jitter(refresh_interval: Duration) -> Duration {
+    // Each `RandomState` is seeded differently, which is random enough for jitter
+    let random = RandomState::new().build_hasher().finish();
+    let ratio = (random % 1000) as f64 / 1000.0 * REFRESH_JITTER_RATIO;
+    refresh_interval + refresh_interval.mul_f64(ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_jitter_delays_by_up_to_a_tenth_of_the_interval() {
+        // Given
+        let refresh_interval = Duration::from_secs(60);
+
+        // When
+        let delays: Vec<Duration> = (0..100).map(|_| with_jitter(refresh_interval)).collect();
+
+        // Then
+        assert!(delays
+            .iter()
+            .all(|delay| *delay >= refresh_interval && *delay < Duration::from_secs(66)));
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+}
@@ -0,0 +1,134 @@
+use super::identity::IdentityContext;
+use super::models::{Flags, SDKTrait};
+use crate::error;
+use flagsmith_flag_engine::identities::Trait;
+use flagsmith_flag_engine::segments::Segment;
+use std::sync::Arc;
+
+// Flag retrieval methods of `Flagsmith`, for code that should not depend on a
+// concrete client, e.g. to use `FakeFlagsmith` (`testing` feature) in tests.
+pub trait FlagProvider {
+    fn get_environment_flags(&self) -> Result<Flags, error::Error>;
+
+    fn get_identity_flags(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<SDKTrait>>,
+        transient: Option<bool>,
+    ) -> Result<Flags, error::Error>;
+
+    fn get_identity_segments(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<Trait>>,
+    ) -> Result<Vec<Segment>, error::Error>;
+
+    fn get_identity_flags_for(&self, identity: IdentityContext) -> Result<Flags, error::Error> {
+        let flags = self.get_identity_flags(
+            &identity.identifier,
+            Some(identity.traits.clone()),
+            Some(identity.transient),
+        )?;
+        Ok(identity.apply_overrides(flags))
+    }
+
+    fn get_identity_segments_for(
+        &self,
+        identity: IdentityContext,
+    ) -> Result<Vec<Segment>, error::Error> {
+        self.get_identity_segments(&identity.identifier, Some(identity.engine_traits()))
+    }
+}
+
+impl FlagProvider for super::Flagsmith {
+    fn get_environment_flags(&self) -> Result<Flags, error::Error> {
+        super::Flagsmith::get_environment_flags(self)
+    }
+
+    fn get_identity_flags(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<SDKTrait>>,
+        transient: Option<bool>,
+    ) -> Result<Flags, error::Error> {
+        super::Flagsmith::get_identity_flags(self, identifier, traits, transient)
+    }
+
+    fn get_identity_segments(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<Trait>>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        super::Flagsmith::get_identity_segments(self, identifier, traits)
+    }
+
+    fn get_identity_flags_for(&self, identity: IdentityContext) -> Result<Flags, error::Error> {
+        super::Flagsmith::get_identity_flags_for(self, identity)
+    }
+
+    fn get_identity_segments_for(
+        &self,
+        identity: IdentityContext,
+    ) -> Result<Vec<Segment>, error::Error> {
+        super::Flagsmith::get_identity_segments_for(self, identity)
+    }
+}
+
+#[cfg(feature = "testing")]
+impl FlagProvider for super::testing::FakeFlagsmith {
+    fn get_environment_flags(&self) -> Result<Flags, error::Error> {
+        super::testing::FakeFlagsmith::get_environment_flags(self)
+    }
+
+    fn get_identity_flags(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<SDKTrait>>,
+        transient: Option<bool>,
+    ) -> Result<Flags, error::Error> {
+        super::testing::FakeFlagsmith::get_identity_flags(self, identifier, traits, transient)
+    }
+
+    fn get_identity_segments(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<Trait>>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        super::testing::FakeFlagsmith::get_identity_segments(self, identifier, traits)
+    }
+}
+
+// Lets a client shared between threads be passed where a provider is expected
+impl<T: FlagProvider + ?Sized> FlagProvider for Arc<T> {
+    fn get_environment_flags(&self) -> Result<Flags, error::Error> {
+        (**self).get_environment_flags()
+    }
+
+    fn get_identity_flags(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<SDKTrait>>,
+        transient: Option<bool>,
+    ) -> Result<Flags, error::Error> {
+        (**self).get_identity_flags(identifier, traits, transient)
+    }
+
+    fn get_identity_segments(
+        &self,
+        identifier: &str,
+        traits: Option<Vec<Trait>>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        (**self).get_identity_segments(identifier, traits)
+    }
+
+    fn get_identity_flags_for(&self, identity: IdentityContext) -> Result<Flags, error::Error> {
+        (**self).get_identity_flags_for(identity)
+    }
+
+    fn get_identity_segments_for(
+        &self,
+        identity: IdentityContext,
+    ) -> Result<Vec<Segment>, error::Error> {
+        (**self).get_identity_segments_for(identity)
+    }
+}
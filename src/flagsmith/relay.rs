@@ -0,0 +1,222 @@
+use super::models::{Flags, SDKTrait};
+use super::Flagsmith;
+use crate::error;
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use serde_json::json;
+
+// Response to a relayed request, always with a JSON body
+#[derive(Debug)]
+pub struct RelayResponse {
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct IdentityRequest {
+    identifier: String,
+    #[serde(default)]
+    traits: Vec<SDKTrait>,
+    #[serde(default)]
+    transient: bool,
+}
+
+// Serves the `flags/`, `identities/` and `environment-document/` endpoints of the
+// Flagsmith API from a client in local evaluation or offline mode, so that services
+// can point their SDK's API URL to the relay instead of the Flagsmith API.
+// Flags are served from the client's environment whatever the `X-Environment-Key`
+// header of the request. As with the Flagsmith API, the environment document, which
+// holds every segment rule and overridden identifier, is only served to requests
+// with a server-side key: the client's own, or the one set with `with_document_key`.
+// # Example
+// ```no_run
+// use flagsmith::flagsmith::relay::Relay;
+// use flagsmith::{Flagsmith, FlagsmithOptions};
+// let flagsmith = Flagsmith::new(
+//     "ser.environment_key".to_string(),
+//     FlagsmithOptions {
+//         enable_local_evaluation: true,
+//         ..Default::default()
+//     },
+// );
+// Relay::new(flagsmith).serve("127.0.0.1:8000").unwrap();
+// ```
+pub struct Relay {
+    flagsmith: Flagsmith,
+    // Key expected in the `X-Environment-Key` header of environment document requests
+    document_key: Option<String>,
+}
+
+impl Relay {
+    pub fn new(flagsmith: Flagsmith) -> Self {
+        let document_key = flagsmith
+            .environment_key()
+            .filter(|environment_key| environment_key.starts_with("ser."))
+            .map(str::to_string);
+        Relay {
+            flagsmith,
+            document_key,
+        }
+    }
+
+    // Serves the environment document to requests with the given key rather than the
+    // client's, e.g. a key dedicated to the relay
+    pub fn with_document_key(mut self, document_key: impl Into<String>) -> Self {
+        self.document_key = Some(document_key.into());
+        self
+    }
+
+    // Answers a single request. `url` is the request path, e.g. `/api/v1/flags/`,
+    // optionally followed by a query string.
+    pub fn handle(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> RelayResponse {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let endpoint = path.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+        match (method, endpoint) {
+            ("GET", "flags") => self.environment_flags(),
+            ("GET", "identities") => {
+                let mut identifier = None;
+                let mut transient = false;
+                for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+                    match key.as_ref() {
+                        "identifier" => identifier = Some(value.into_owned()),
+                        "transient" => transient = value == "true",
+                        _ => {}
+                    }
+                }
+                match identifier {
+                    Some(identifier) => self.identity_flags(IdentityRequest {
+                        identifier,
+                        traits: vec![],
+                        transient,
+                    }),
+                    None => error_response(400, "Missing identifier query parameter."),
+                }
+            }
+            ("POST", "identities") => match serde_json::from_slice(body) {
+                Ok(request) => self.identity_flags(request),
+                Err(e) => error_response(400, &format!("Invalid identity request: {}", e)),
+            },
+            ("GET", "environment-document") => self.environment_document(headers),
+            (_, "flags" | "identities" | "environment-document") => {
+                error_response(405, "Method not allowed.")
+            }
+            _ => error_response(404, "Not found."),
+        }
+    }
+
+    // Answers requests on the given address until the server fails to start
+    pub fn serve(&self, address: &str) -> Result<(), error::Error> {
+        let server = tiny_http::Server::http(address).map_err(|e| {
+            error::Error::new(
+                error::ErrorKind::FlagsmithClientError,
+                format!("Failed to start relay on {}: {}", address, e),
+            )
+        })?;
+        let content_type: tiny_http::Header = "Content-Type: application/json".parse().unwrap();
+        for mut request in server.incoming_requests() {
+            let mut body = vec![];
+            let headers = request_headers(&request);
+            let response = match request.as_reader().read_to_end(&mut body) {
+                Ok(_) => self.handle(request.method().as_str(), request.url(), &headers, &body),
+                Err(e) => error_response(400, &format!("Failed to read request: {}", e)),
+            };
+            let response = tiny_http::Response::from_string(response.body.to_string())
+                .with_status_code(response.status)
+                .with_header(content_type.clone());
+            if let Err(e) = request.respond(response) {
+                log::warn!("Failed to send relay response: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn environment_document(&self, headers: &HeaderMap) -> RelayResponse {
+        let environment_key = headers
+            .get("X-Environment-Key")
+            .and_then(|value| value.to_str().ok());
+        if self.document_key.is_none() || environment_key != self.document_key.as_deref() {
+            return error_response(401, "Invalid or missing server-side environment key.");
+        }
+        match self.flagsmith.get_environment_document_json() {
+            Some(environment_document) => RelayResponse {
+                status: 200,
+                body: (*environment_document).clone(),
+            },
+            None => error_response(503, "Environment not loaded yet."),
+        }
+    }
+
+    fn environment_flags(&self) -> RelayResponse {
+        match self.flagsmith.get_environment_flags() {
+            Ok(flags) => RelayResponse {
+                status: 200,
+                body: api_flags(&flags),
+            },
+            Err(e) => flagsmith_error_response(e),
+        }
+    }
+
+    fn identity_flags(&self, request: IdentityRequest) -> RelayResponse {
+        let flags = self.flagsmith.get_identity_flags(
+            &request.identifier,
+            Some(request.traits.clone()),
+            Some(request.transient),
+        );
+        match flags {
+            Ok(flags) => RelayResponse {
+                status: 200,
+                body: json!({
+                    "identifier": request.identifier,
+                    "flags": api_flags(&flags),
+                    "traits": request.traits,
+                }),
+            },
+            Err(e) => flagsmith_error_response(e),
+        }
+    }
+}
+
+// Headers of the request, skipping those that are not valid HTTP headers
+fn request_headers(request: &tiny_http::Request) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for header in request.headers() {
+        let name = HeaderName::from_bytes(header.field.as_str().as_str().as_bytes());
+        let value = HeaderValue::from_str(header.value.as_str());
+        if let (Ok(name), Ok(value)) = (name, value) {
+            headers.append(name, value);
+        }
+    }
+    headers
+}
+
+// Flags served by the default flag handler are not part of the environment
+fn api_flags(flags: &Flags) -> serde_json::Value {
+    let mut flags: Vec<_> = flags
+        .all_flags()
+        .into_iter()
+        .filter(|flag| !flag.is_default)
+        .collect();
+    flags.sort_by_key(|flag| flag.feature_id);
+    flags.iter().map(|flag| flag.to_api_flag()).collect()
+}
+
+fn flagsmith_error_response(e: error::Error) -> RelayResponse {
+    let status = match e.kind {
+        error::ErrorKind::FlagsmithAPIError => 502,
+        error::ErrorKind::FlagsmithClientError => 503,
+    };
+    error_response(status, &e.msg)
+}
+
+fn error_response(status: u16, detail: &str) -> RelayResponse {
+    RelayResponse {
+        status,
+        body: json!({ "detail": detail }),
+    }
+}
@@ -0,0 +1,79 @@
+use super::identity::IdentityContext;
+use super::models::Flags;
+use super::Flagsmith;
+use crate::error;
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use std::time::Duration;
+
+// Where the flags of a single call are evaluated, regardless of the client's mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvaluationMode {
+    // Against the environment document, failing if it has not been loaded
+    Local,
+    // Through the Flagsmith API, even if the environment document has been loaded
+    Remote,
+}
+
+// Options of the calls made through `Flagsmith::with_request_options`
+#[derive(Clone, Debug, Default)]
+pub struct RequestOptions {
+    // Replaces `request_timeout_seconds`
+    pub timeout: Option<Duration>,
+    // Sent along with `custom_headers`, replacing those of the same name
+    pub headers: HeaderMap,
+    // Defaults to the client's mode
+    pub evaluation_mode: Option<EvaluationMode>,
+}
+
+impl RequestOptions {
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_evaluation_mode(mut self, evaluation_mode: EvaluationMode) -> Self {
+        self.evaluation_mode = Some(evaluation_mode);
+        self
+    }
+}
+
+// Client making its calls with the given request options, e.g. to propagate a
+// trace header or to set a tighter deadline for a single call.
+// # Example
+// ```
+// let options = RequestOptions::default().with_timeout(Duration::from_millis(200));
+// let flags = flagsmith
+//     .with_request_options(options)
+//     .get_identity_flags_for("user_1");
+// ```
+pub struct ScopedFlagsmith<'a> {
+    pub(super) flagsmith: &'a Flagsmith,
+    pub(super) options: RequestOptions,
+}
+
+impl ScopedFlagsmith<'_> {
+    pub fn get_environment_flags(&self) -> Result<Flags, error::Error> {
+        self.flagsmith.get_environment_flags_with(&self.options)
+    }
+
+    pub fn get_identity_flags_for(
+        &self,
+        identity: impl Into<IdentityContext>,
+    ) -> Result<Flags, error::Error> {
+        self.flagsmith
+            .get_identity_flags_with(identity.into(), &self.options)
+    }
+
+    pub fn get_identities_flags_for(
+        &self,
+        identities: &[IdentityContext],
+    ) -> Result<Vec<Flags>, error::Error> {
+        self.flagsmith
+            .get_identities_flags_with(identities, &self.options)
+    }
+}
@@ -0,0 +1,132 @@
+use super::telemetry;
+use crate::error;
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+// Where the client gets its flags from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClientMode {
+    // Every call hits the Flagsmith API
+    Remote,
+    // Environment flags are served from a cache refreshed in the background
+    CachedRemote,
+    // Flags are evaluated against an environment document refreshed in the background
+    LocalEvaluation,
+    // Flags are evaluated against the environment provided by the offline handler
+    Offline,
+}
+
+// Snapshot of the client health, as returned by `Flagsmith::status`
+#[derive(Clone, Debug)]
+pub struct ClientStatus {
+    pub mode: ClientMode,
+    // Time of the last successful environment (or environment flags) refresh
+    pub last_successful_refresh: Option<DateTime<Utc>>,
+    // Error of the last refresh, cleared by the next successful one
+    pub last_error: Option<String>,
+    pub consecutive_failures: u32,
+    // `updated_at` of the environment document currently in use, if known
+    pub environment_updated_at: Option<DateTime<Utc>>,
+    // Number of flag evaluations waiting to be sent to the analytics endpoint
+    pub analytics_queue_depth: usize,
+}
+
+impl ClientStatus {
+    // Whether the client can serve flags without calling the API on demand, i.e. the
+    // environment (or the environment flags cache) has been loaded at least once.
+    // Always true in remote evaluation mode.
+    pub fn is_ready(&self) -> bool {
+        match self.mode {
+            ClientMode::Remote => true,
+            _ => self.last_successful_refresh.is_some(),
+        }
+    }
+}
+
+// Outcome of the background refreshes, kept in the datastore
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RefreshStatus {
+    pub(crate) last_successful_refresh: Option<DateTime<Utc>>,
+    pub(crate) last_error: Option<String>,
+    pub(crate) consecutive_failures: u32,
+    pub(crate) environment_updated_at: Option<DateTime<Utc>>,
+}
+
+impl RefreshStatus {
+    pub(crate) fn record_success(&mut self, environment_updated_at: Option<DateTime<Utc>>) {
+        telemetry::record_refresh(true);
+        self.last_successful_refresh = Some(Utc::now());
+        self.last_error = None;
+        self.consecutive_failures = 0;
+        self.environment_updated_at = environment_updated_at;
+    }
+
+    pub(crate) fn record_failure(&mut self, error: &error::Error) {
+        telemetry::record_refresh(false);
+        self.last_error = Some(error.to_string());
+        self.consecutive_failures += 1;
+    }
+}
+
+// Reads `updated_at` from a raw environment document. The API has been seen to
+// return both RFC 3339 timestamps and naive ones, which are assumed to be UTC.
+pub(crate) fn parse_updated_at(environment_document: &serde_json::Value) -> Option<DateTime<Utc>> {
+    let updated_at = environment_document["updated_at"].as_str()?;
+    if let Ok(updated_at) = DateTime::parse_from_rfc3339(updated_at) {
+        return Some(updated_at.with_timezone(&Utc));
+    }
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(updated_at, format).ok())
+        .map(|updated_at| updated_at.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_updated_at_accepts_naive_and_rfc3339_timestamps() {
+        // Given
+        let expected = Utc.with_ymd_and_hms(2023, 7, 14, 16, 12, 0).unwrap();
+
+        // Then
+        for updated_at in [
+            "2023-07-14 16:12:00.000000",
+            "2023-07-14T16:12:00.000000",
+            "2023-07-14T16:12:00Z",
+            "2023-07-14T18:12:00+02:00",
+        ] {
+            let document = serde_json::json!({ "updated_at": updated_at });
+            assert_eq!(
+                parse_updated_at(&document),
+                Some(expected),
+                "{}",
+                updated_at
+            );
+        }
+        assert_eq!(parse_updated_at(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn record_success_resets_failures() {
+        // Given
+        let mut status = RefreshStatus::default();
+        let error = error::Error::new(error::ErrorKind::FlagsmithAPIError, "boom".to_string());
+        status.record_failure(&error);
+        status.record_failure(&error);
+        assert_eq!(status.consecutive_failures, 2);
+        assert_eq!(
+            status.last_error.as_deref(),
+            Some("Flagsmith API error: boom")
+        );
+
+        // When
+        status.record_success(None);
+
+        // Then
+        assert_eq!(status.consecutive_failures, 0);
+        assert!(status.last_error.is_none());
+        assert!(status.last_successful_refresh.is_some());
+    }
+}
@@ -0,0 +1,463 @@
+// Spans emitted with the `tracing` feature and metrics recorded through the
+// `metrics` crate with the `metrics` feature. Without them, both are no-ops.
+use super::models::Flags;
+use super::status::ClientMode;
+use crate::error;
+use std::time::Duration;
+
+#[cfg(feature = "tracing")]
+pub(crate) use self::spans::*;
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) use self::noop::*;
+
+#[cfg(feature = "metrics")]
+pub(crate) use self::recorded_metrics::*;
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) use self::noop_metrics::*;
+
+// Identifiers are hashed so that traces do not carry user identifiers. The hash is
+// the first 8 bytes of the SHA-256 of the salted identifier, stable across builds
+// and platforms so that traces can be correlated.
+#[cfg(feature = "tracing")]
+fn hash_identifier(identifier: &str, salt: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::new()
+        .chain_update(salt)
+        .chain_update(identifier)
+        .finalize();
+    digest[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(feature = "tracing")]
+mod spans {
+    use super::*;
+    use tracing::field::Empty;
+    use tracing::span::EnteredSpan;
+
+    pub(crate) struct Span(EnteredSpan);
+
+    pub(crate) fn environment_flags_span(mode: ClientMode) -> Span {
+        Span(
+            tracing::info_span!(
+                "flagsmith.get_environment_flags",
+                mode = ?mode,
+                feature_count = Empty,
+            )
+            .entered(),
+        )
+    }
+
+    pub(crate) fn identity_flags_span(mode: ClientMode, identifier: &str, salt: &str) -> Span {
+        Span(
+            tracing::info_span!(
+                "flagsmith.get_identity_flags",
+                mode = ?mode,
+                identifier_hash = %hash_identifier(identifier, salt),
+                feature_count = Empty,
+            )
+            .entered(),
+        )
+    }
+
+    pub(crate) fn update_environment_span() -> Span {
+        Span(
+            tracing::info_span!(
+                "flagsmith.update_environment",
+                feature_count = Empty,
+                success = Empty,
+            )
+            .entered(),
+        )
+    }
+
+    pub(crate) fn analytics_flush_span(feature_count: usize) -> Span {
+        Span(
+            tracing::info_span!(
+                "flagsmith.analytics_flush",
+                feature_count,
+                http.status_code = Empty,
+                latency_ms = Empty,
+            )
+            .entered(),
+        )
+    }
+
+    pub(crate) fn http_span(method: &http::Method, url: &str) -> Span {
+        Span(
+            tracing::info_span!(
+                "flagsmith.http",
+                http.method = %method,
+                http.url = url,
+                http.status_code = Empty,
+                latency_ms = Empty,
+            )
+            .entered(),
+        )
+    }
+
+    impl Span {
+        // Records the number of flags returned, passing the result through
+        pub(crate) fn record_flags(
+            &self,
+            result: Result<Flags, error::Error>,
+        ) -> Result<Flags, error::Error> {
+            if let Ok(flags) = &result {
+                self.0.record("feature_count", flags.as_map().len());
+            }
+            result
+        }
+
+        pub(crate) fn record_update(&self, result: &Result<usize, error::Error>) {
+            if let Ok(feature_count) = result {
+                self.0.record("feature_count", feature_count);
+            }
+            self.0.record("success", result.is_ok());
+        }
+
+        pub(crate) fn record_http(&self, status_code: Option<u16>, latency: Duration) {
+            if let Some(status_code) = status_code {
+                self.0.record("http.status_code", status_code);
+            }
+            self.0.record("latency_ms", latency.as_millis() as u64);
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod noop {
+    use super::*;
+
+    pub(crate) struct Span;
+
+    pub(crate) fn environment_flags_span(_mode: ClientMode) -> Span {
+        Span
+    }
+
+    pub(crate) fn identity_flags_span(_mode: ClientMode, _identifier: &str, _salt: &str) -> Span {
+        Span
+    }
+
+    pub(crate) fn update_environment_span() -> Span {
+        Span
+    }
+
+    pub(crate) fn analytics_flush_span(_feature_count: usize) -> Span {
+        Span
+    }
+
+    pub(crate) fn http_span(_method: &http::Method, _url: &str) -> Span {
+        Span
+    }
+
+    impl Span {
+        pub(crate) fn record_flags(
+            &self,
+            result: Result<Flags, error::Error>,
+        ) -> Result<Flags, error::Error> {
+            result
+        }
+
+        pub(crate) fn record_update(&self, _result: &Result<usize, error::Error>) {}
+
+        pub(crate) fn record_http(&self, _status_code: Option<u16>, _latency: Duration) {}
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod recorded_metrics {
+    use super::*;
+
+    // Counts requests by endpoint (e.g. `flags`, `identities`) and status code, or
+    // `error` when no response was received, and records their latency
+    pub(crate) fn record_http_request(endpoint: &str, status_code: Option<u16>, latency: Duration) {
+        let status = status_code.map_or("error".to_string(), |status_code| status_code.to_string());
+        metrics::counter!(
+            "flagsmith_http_requests_total",
+            "endpoint" => endpoint.to_string(),
+            "status" => status,
+        )
+        .increment(1);
+        metrics::histogram!(
+            "flagsmith_http_request_duration_seconds",
+            "endpoint" => endpoint.to_string(),
+        )
+        .record(latency.as_secs_f64());
+    }
+
+    pub(crate) fn record_refresh(success: bool) {
+        let result = if success { "success" } else { "failure" };
+        metrics::counter!("flagsmith_environment_refreshes_total", "result" => result).increment(1);
+    }
+
+    // Counts calls to `get_environment_flags` (`kind` "environment") and
+    // `get_identity_flags` (`kind` "identity")
+    pub(crate) fn record_evaluation(kind: &'static str, mode: ClientMode, success: bool) {
+        let result = if success { "success" } else { "failure" };
+        metrics::counter!(
+            "flagsmith_evaluations_total",
+            "kind" => kind,
+            "mode" => mode_label(mode),
+            "result" => result,
+        )
+        .increment(1);
+    }
+
+    pub(crate) fn record_flag_evaluation(feature_name: &str) {
+        metrics::counter!(
+            "flagsmith_flag_evaluations_total",
+            "feature" => feature_name.to_string(),
+        )
+        .increment(1);
+    }
+
+    // `reason` is "api_error" when the API could not be reached, "missing_flag"
+    // when a flag unknown to the environment was asked for
+    pub(crate) fn record_default_flag_fallback(reason: &'static str) {
+        metrics::counter!("flagsmith_default_flag_fallbacks_total", "reason" => reason)
+            .increment(1);
+    }
+
+    pub(crate) fn record_analytics_flush(success: bool) {
+        let result = if success { "success" } else { "failure" };
+        metrics::counter!("flagsmith_analytics_flushes_total", "result" => result).increment(1);
+    }
+
+    fn mode_label(mode: ClientMode) -> &'static str {
+        match mode {
+            ClientMode::Remote => "remote",
+            ClientMode::CachedRemote => "cached_remote",
+            ClientMode::LocalEvaluation => "local_evaluation",
+            ClientMode::Offline => "offline",
+        }
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+mod noop_metrics {
+    use super::*;
+
+    pub(crate) fn record_http_request(
+        _endpoint: &str,
+        _status_code: Option<u16>,
+        _latency: Duration,
+    ) {
+    }
+
+    pub(crate) fn record_refresh(_success: bool) {}
+
+    pub(crate) fn record_evaluation(_kind: &'static str, _mode: ClientMode, _success: bool) {}
+
+    pub(crate) fn record_flag_evaluation(_feature_name: &str) {}
+
+    pub(crate) fn record_default_flag_fallback(_reason: &'static str) {}
+
+    pub(crate) fn record_analytics_flush(_success: bool) {}
+}
+
+// Last path segment of an API URL, e.g. `environment-document`
+pub(crate) fn endpoint_label(url: &str) -> &str {
+    let path = url.split('?').next().unwrap_or(url).trim_end_matches('/');
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tracing_tests {
+    use super::*;
+
+    #[test]
+    fn hash_identifier_does_not_leak_identifier() {
+        // When
+        let hash = hash_identifier("user@example.com", "");
+
+        // Then
+        assert_eq!(hash.len(), 16);
+        assert!(!hash.contains("user"));
+        // SHA-256 of "user@example.com", truncated
+        assert_eq!(hash, "b4c9a289323b21a0");
+        assert_ne!(hash, hash_identifier("user@example.com", "salt"));
+    }
+
+    // Records the names of the spans created and the fields recorded on them
+    #[derive(Default)]
+    struct RecordingSubscriber {
+        events: std::sync::Mutex<Vec<String>>,
+    }
+
+    struct FieldNames<'a>(&'a mut Vec<String>);
+
+    impl tracing::field::Visit for FieldNames<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, _value: &dyn std::fmt::Debug) {
+            self.0.push(field.name().to_string());
+        }
+    }
+
+    impl tracing::Subscriber for RecordingSubscriber {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut events = self.events.lock().unwrap();
+            events.push(span.metadata().name().to_string());
+            tracing::span::Id::from_u64(events.len() as u64)
+        }
+        fn record(&self, _span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+            values.record(&mut FieldNames(&mut self.events.lock().unwrap()));
+        }
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+        fn event(&self, _event: &tracing::Event<'_>) {}
+        fn enter(&self, _span: &tracing::span::Id) {}
+        fn exit(&self, _span: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn http_span_records_status_and_latency() {
+        // Given
+        let subscriber = std::sync::Arc::new(RecordingSubscriber::default());
+
+        // When
+        tracing::subscriber::with_default(std::sync::Arc::clone(&subscriber), || {
+            let span = http_span(&http::Method::GET, "http://localhost/flags/");
+            span.record_http(Some(200), Duration::from_millis(5));
+        });
+
+        // Then
+        assert_eq!(
+            *subscriber.events.lock().unwrap(),
+            vec!["flagsmith.http", "http.status_code", "latency_ms"]
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_label_is_last_path_segment() {
+        assert_eq!(
+            endpoint_label("https://edge.api.flagsmith.com/api/v1/environment-document/"),
+            "environment-document"
+        );
+        assert_eq!(
+            endpoint_label("http://localhost/api/v1/identities/?identifier=foo"),
+            "identities"
+        );
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod metrics_tests {
+    use super::*;
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    // Keeps the counters registered, keyed by name and labels
+    #[derive(Default)]
+    struct CountingRecorder {
+        counters: Mutex<Vec<(Key, Arc<AtomicU64>)>>,
+    }
+
+    struct AtomicCounter(Arc<AtomicU64>);
+
+    impl CounterFn for AtomicCounter {
+        fn increment(&self, value: u64) {
+            self.0.fetch_add(value, Ordering::SeqCst);
+        }
+        fn absolute(&self, value: u64) {
+            self.0.store(value, Ordering::SeqCst);
+        }
+    }
+
+    impl CountingRecorder {
+        fn count(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+            self.counters
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(key, _)| {
+                    key.name() == name
+                        && labels.iter().all(|(label, value)| {
+                            key.labels().any(|key_label| {
+                                key_label.key() == *label && key_label.value() == *value
+                            })
+                        })
+                })
+                .map(|(_, count)| count.load(Ordering::SeqCst))
+                .sum()
+        }
+    }
+
+    impl Recorder for CountingRecorder {
+        fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {
+        }
+        fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+        fn describe_histogram(
+            &self,
+            _key: KeyName,
+            _unit: Option<Unit>,
+            _description: SharedString,
+        ) {
+        }
+        fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+            let count = Arc::new(AtomicU64::new(0));
+            self.counters
+                .lock()
+                .unwrap()
+                .push((key.clone(), Arc::clone(&count)));
+            Counter::from_arc(Arc::new(AtomicCounter(count)))
+        }
+        fn register_gauge(&self, _key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+        fn register_histogram(&self, _key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    fn records_requests_refreshes_and_fallbacks() {
+        // Given
+        let recorder = CountingRecorder::default();
+
+        // When
+        metrics::with_local_recorder(&recorder, || {
+            record_http_request("flags", Some(200), Duration::from_millis(5));
+            record_http_request("flags", None, Duration::from_millis(5));
+            record_refresh(false);
+            record_evaluation("identity", ClientMode::LocalEvaluation, true);
+            record_default_flag_fallback("api_error");
+        });
+
+        // Then
+        let http_requests = "flagsmith_http_requests_total";
+        assert_eq!(recorder.count(http_requests, &[("endpoint", "flags")]), 2);
+        assert_eq!(recorder.count(http_requests, &[("status", "error")]), 1);
+        assert_eq!(
+            recorder.count(
+                "flagsmith_environment_refreshes_total",
+                &[("result", "failure")]
+            ),
+            1
+        );
+        assert_eq!(
+            recorder.count(
+                "flagsmith_evaluations_total",
+                &[("kind", "identity"), ("mode", "local_evaluation")]
+            ),
+            1
+        );
+        assert_eq!(
+            recorder.count("flagsmith_default_flag_fallbacks_total", &[]),
+            1
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use super::models::{Flag, Flags, SDKTrait};
+use crate::error;
+use flagsmith_flag_engine::identities::Trait;
+use flagsmith_flag_engine::segments::Segment;
+use flagsmith_flag_engine::types::FlagsmithValue;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct FakeState {
+    environment_flags: HashMap<String, Flag>,
+    identity_flags: HashMap<String, HashMap<String, Flag>>,
+    identity_segments: HashMap<String, Vec<Segment>>,
+    reads: Vec<String>,
+}
+
+// In-memory stand-in for `Flagsmith`, for testing code that reads flags without
+// running a Flagsmith API.
+// # Example
+// ```
+// use flagsmith::flagsmith::testing::FakeFlagsmith;
+// use flagsmith_flag_engine::types::FlagsmithValue;
+// let flagsmith = FakeFlagsmith::new();
+// flagsmith.set_environment_flag("my_feature", true, FlagsmithValue::default());
+// let flags = flagsmith.get_environment_flags().unwrap();
+// assert!(flags.is_feature_enabled("my_feature").unwrap());
+// assert!(flagsmith.was_read("my_feature"));
+// ```
+pub struct FakeFlagsmith {
+    state: Mutex<FakeState>,
+    default_flag_handler: Option<fn(&str) -> Flag>,
+    // Flags returned by the fake report their reads here, including those of unset flags
+    reads_tx: flume::Sender<String>,
+    reads_rx: flume::Receiver<String>,
+}
+
+impl Default for FakeFlagsmith {
+    fn default() -> Self {
+        FakeFlagsmith::new()
+    }
+}
+
+impl FakeFlagsmith {
+    pub fn new() -> Self {
+        let (reads_tx, reads_rx) = flume::unbounded();
+        FakeFlagsmith {
+            state: Mutex::new(FakeState::default()),
+            default_flag_handler: None,
+            reads_tx,
+            reads_rx,
+        }
+    }
+
+    // Used for the flags that are not set, as `FlagsmithOptions.default_flag_handler`
+    pub fn with_default_flag_handler(mut self, default_flag_handler: fn(&str) -> Flag) -> Self {
+        self.default_flag_handler = Some(default_flag_handler);
+        self
+    }
+
+    // Sets an environment flag, also served to every identity unless overridden
+    // with `set_identity_flag`
+    pub fn set_environment_flag(&self, feature_name: &str, enabled: bool, value: FlagsmithValue) {
+        let mut state = self.state.lock().unwrap();
+        let feature_id = next_feature_id(&state, feature_name);
+        state.environment_flags.insert(
+            feature_name.to_string(),
+            fake_flag(feature_id, feature_name, enabled, value),
+        );
+    }
+
+    pub fn set_identity_flag(
+        &self,
+        identifier: &str,
+        feature_name: &str,
+        enabled: bool,
+        value: FlagsmithValue,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let feature_id = next_feature_id(&state, feature_name);
+        state
+            .identity_flags
+            .entry(identifier.to_string())
+            .or_default()
+            .insert(
+                feature_name.to_string(),
+                fake_flag(feature_id, feature_name, enabled, value),
+            );
+    }
+
+    // Makes the identity a member of the given segment, which has no rules
+    pub fn add_identity_segment(&self, identifier: &str, segment_name: &str) {
+        let mut state = self.state.lock().unwrap();
+        let segment_id = next_segment_id(&state, segment_name);
+        state
+            .identity_segments
+            .entry(identifier.to_string())
+            .or_default()
+            .push(Segment {
+                id: segment_id,
+                name: segment_name.to_string(),
+                rules: vec![],
+                feature_states: vec![],
+            });
+    }
+
+    // Removes every flag and segment membership, along with the recorded reads
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = FakeState::default();
+        self.reads_rx.drain();
+    }
+
+    pub fn get_environment_flags(&self) -> Result<Flags, error::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(self.flags(state.environment_flags.clone()))
+    }
+
+    pub fn get_identity_flags(
+        &self,
+        identifier: &str,
+        _traits: Option<Vec<SDKTrait>>,
+        _transient: Option<bool>,
+    ) -> Result<Flags, error::Error> {
+        let state = self.state.lock().unwrap();
+        let mut flags = state.environment_flags.clone();
+        if let Some(identity_flags) = state.identity_flags.get(identifier) {
+            flags.extend(identity_flags.clone());
+        }
+        Ok(self.flags(flags))
+    }
+
+    pub fn get_identity_segments(
+        &self,
+        identifier: &str,
+        _traits: Option<Vec<Trait>>,
+    ) -> Result<Vec<Segment>, error::Error> {
+        let state = self.state.lock().unwrap();
+        Ok(state
+            .identity_segments
+            .get(identifier)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    // Names of the features read through `Flags::get_flag` and the methods built on
+    // it, in order, one entry per read, whether the flag is set or not
+    pub fn read_flags(&self) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        state.reads.extend(self.reads_rx.drain());
+        state.reads.clone()
+    }
+
+    pub fn was_read(&self, feature_name: &str) -> bool {
+        self.read_flags()
+            .iter()
+            .any(|read_feature_name| read_feature_name == feature_name)
+    }
+
+    fn flags(&self, flags: HashMap<String, Flag>) -> Flags {
+        Flags::from_map(flags, None, self.default_flag_handler).with_reads_tx(self.reads_tx.clone())
+    }
+}
+
+// Keeps the feature id of a feature stable across environment and identity flags
+fn next_feature_id(state: &FakeState, feature_name: &str) -> u32 {
+    let existing_flag = state.environment_flags.get(feature_name).or_else(|| {
+        state
+            .identity_flags
+            .values()
+            .find_map(|identity_flags| identity_flags.get(feature_name))
+    });
+    match existing_flag {
+        Some(flag) => flag.feature_id,
+        None => {
+            let mut feature_names: Vec<&String> = state.environment_flags.keys().collect();
+            for identity_flags in state.identity_flags.values() {
+                feature_names.extend(identity_flags.keys());
+            }
+            feature_names.sort();
+            feature_names.dedup();
+            feature_names.len() as u32 + 1
+        }
+    }
+}
+
+// Keeps the segment id of a segment stable across the identities it is added to
+fn next_segment_id(state: &FakeState, segment_name: &str) -> u32 {
+    let segments = state.identity_segments.values().flatten();
+    let existing_segment = segments
+        .clone()
+        .find(|segment| segment.name == segment_name);
+    match existing_segment {
+        Some(segment) => segment.id,
+        None => {
+            let mut segment_names: Vec<&String> = segments.map(|segment| &segment.name).collect();
+            segment_names.sort();
+            segment_names.dedup();
+            segment_names.len() as u32 + 1
+        }
+    }
+}
+
+fn fake_flag(feature_id: u32, feature_name: &str, enabled: bool, value: FlagsmithValue) -> Flag {
+    Flag {
+        enabled,
+        value,
+        is_default: false,
+        feature_id,
+        feature_name: feature_name.to_string(),
+    }
+}
@@ -0,0 +1,142 @@
+use crate::error;
+use http::{HeaderMap, Method};
+use std::fmt;
+use std::time::Duration;
+
+// Proxy for the requests to the Flagsmith API, e.g. `http://proxy.internal:3128`,
+// authenticating with basic auth if `username` is set
+#[derive(Clone, Default)]
+pub struct ProxyOptions {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+// Keeps the password out of logs
+impl fmt::Debug for ProxyOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProxyOptions")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+// PEM encoded certificate chain and private key presented for mutual TLS
+#[derive(Clone, Default)]
+pub struct ClientCertificate {
+    pub certificate_pem: Vec<u8>,
+    pub private_key_pem: Vec<u8>,
+}
+
+// Request to the Flagsmith API, holding every header to send: `custom_headers`, the
+// SDK headers, the environment key and the headers of the request options
+#[derive(Clone, Debug)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+    pub timeout: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+// Sends the requests of the client, set with `FlagsmithOptions.transport`, e.g. to
+// use an HTTP client configured by the application. Responses are returned whatever
+// their status; errors are for requests that could not be sent, and should be of
+// kind `FlagsmithAPIError`.
+pub trait HttpTransport: Send + Sync {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse, error::Error>;
+}
+
+// Transport used when `FlagsmithOptions.transport` is not set
+#[cfg(feature = "reqwest")]
+#[derive(Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::blocking::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    pub fn new(client: reqwest::blocking::Client) -> Self {
+        ReqwestTransport { client }
+    }
+
+    // Transport configured with the proxy, certificates and connect timeout of the options
+    pub fn from_options(flagsmith_options: &super::FlagsmithOptions) -> Result<Self, error::Error> {
+        let mut builder = reqwest::blocking::Client::builder();
+        if let Some(proxy_options) = &flagsmith_options.proxy {
+            let mut proxy = reqwest::Proxy::all(&proxy_options.url).map_err(invalid_option)?;
+            if let Some(username) = &proxy_options.username {
+                proxy = proxy.basic_auth(username, proxy_options.password.as_deref().unwrap_or(""));
+            }
+            builder = builder.proxy(proxy);
+        }
+        let mut root_certificates = vec![];
+        for pem in &flagsmith_options.root_certificates {
+            let certificates =
+                reqwest::Certificate::from_pem_bundle(pem).map_err(invalid_option)?;
+            if certificates.is_empty() {
+                return Err(error::Error::new(
+                    error::ErrorKind::FlagsmithClientError,
+                    "No certificate found in root certificate PEM.".to_string(),
+                ));
+            }
+            root_certificates.extend(certificates);
+        }
+        if !root_certificates.is_empty() {
+            builder = builder.tls_certs_merge(root_certificates);
+        }
+        if let Some(client_certificate) = &flagsmith_options.client_certificate {
+            let pem = [
+                client_certificate.certificate_pem.as_slice(),
+                b"\n",
+                client_certificate.private_key_pem.as_slice(),
+            ]
+            .concat();
+            builder = builder.identity(reqwest::Identity::from_pem(&pem).map_err(invalid_option)?);
+        }
+        if let Some(connect_timeout_seconds) = flagsmith_options.connect_timeout_seconds {
+            builder = builder.connect_timeout(Duration::from_secs(connect_timeout_seconds));
+        }
+        Ok(ReqwestTransport::new(
+            builder.build().map_err(invalid_option)?,
+        ))
+    }
+}
+
+#[cfg(feature = "reqwest")]
+fn invalid_option(e: reqwest::Error) -> error::Error {
+    error::Error::new(error::ErrorKind::FlagsmithClientError, e.to_string())
+}
+
+#[cfg(feature = "reqwest")]
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse, error::Error> {
+        let mut builder = self
+            .client
+            .request(request.method, request.url)
+            .headers(request.headers)
+            .timeout(request.timeout);
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        let response = builder.send()?;
+        Ok(HttpResponse {
+            status: response.status().as_u16(),
+            body: response.bytes()?.to_vec(),
+        })
+    }
+}
@@ -1,4 +1,6 @@
 pub mod error;
 pub mod flagsmith;
+pub use crate::flagsmith::identity::IdentityContext;
 pub use crate::flagsmith::models::Flag;
+pub use crate::flagsmith::provider::FlagProvider;
 pub use crate::flagsmith::{Flagsmith, FlagsmithOptions};
@@ -0,0 +1,197 @@
+use flagsmith::flagsmith::offline_handler::{LocalFileHandler, OfflineHandler};
+
+use httpmock::prelude::*;
+use rstest::*;
+use std::process::{Command, Output};
+
+mod fixtures;
+
+use fixtures::environment_json;
+use fixtures::mock_server;
+use fixtures::ENVIRONMENT_KEY;
+
+static ENVIRONMENT_DOCUMENT: &str = "tests/fixtures/environment.json";
+
+fn flagsmith_cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_flagsmith-cli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[rstest]
+fn test_cli_prints_environment_flags_as_table() {
+    // When
+    let output = flagsmith_cli(&["environment-flags", "--document", ENVIRONMENT_DOCUMENT]);
+
+    // Then
+    assert_eq!(
+        stdout(&output),
+        "FEATURE    ENABLED  VALUE\nfeature_1  true     some_value\n"
+    );
+}
+
+#[rstest]
+fn test_cli_prints_identity_flags_as_json() {
+    // When
+    let output = flagsmith_cli(&[
+        "identity-flags",
+        "--document",
+        ENVIRONMENT_DOCUMENT,
+        "--identifier",
+        "some_identity",
+        "--format",
+        "json",
+    ]);
+
+    // Then
+    let flags: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(
+        flags,
+        serde_json::json!([{
+            "feature": {"id": fixtures::FEATURE_1_ID, "name": fixtures::FEATURE_1_NAME},
+            "enabled": true,
+            "feature_state_value": fixtures::FEATURE_1_STR_VALUE,
+        }])
+    );
+}
+
+#[rstest]
+#[case(&["--trait", "foo=bar"], "1   Test Segment")]
+#[case(&["--traits-json", r#"{"foo": "bar"}"#], "1   Test Segment")]
+#[case(&["--trait", "foo=baz"], "")]
+fn test_cli_lists_identity_segments(#[case] trait_args: &[&str], #[case] expected_row: &str) {
+    // Given
+    let mut args = vec![
+        "segments",
+        "--document",
+        ENVIRONMENT_DOCUMENT,
+        "--identifier",
+        "some_identity",
+    ];
+    args.extend(trait_args);
+
+    // When
+    let output = flagsmith_cli(&args);
+
+    // Then
+    let stdout = stdout(&output);
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("ID  NAME"));
+    assert_eq!(lines.next().unwrap_or(""), expected_row);
+}
+
+#[rstest]
+#[case(&["environment-flags", "--document", "tests/fixtures/missing.json"])]
+#[case(&["segments", "--document", ENVIRONMENT_DOCUMENT, "--identifier", "id", "--traits-json", "[]"])]
+#[case(&["segments", "--document", ENVIRONMENT_DOCUMENT, "--identifier", "id", "--trait", "foo"])]
+fn test_cli_fails_on_invalid_input(#[case] args: &[&str]) {
+    // When
+    let output = flagsmith_cli(args);
+
+    // Then
+    assert!(!output.status.success());
+    assert!(!output.stderr.is_empty());
+}
+
+#[rstest]
+fn test_cli_fetches_environment_document(
+    mock_server: MockServer,
+    environment_json: serde_json::Value,
+) {
+    // Given
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/environment-document/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY);
+        then.status(200).json_body(environment_json.clone());
+    });
+    let output_path = std::env::temp_dir().join("flagsmith_cli_test_environment.json");
+
+    // When
+    let output = flagsmith_cli(&[
+        "fetch",
+        "--environment-key",
+        ENVIRONMENT_KEY,
+        "--api-url",
+        &mock_server.url("/api/v1/"),
+        "--output",
+        output_path.to_str().unwrap(),
+    ]);
+
+    // Then
+    stdout(&output);
+    api_mock.assert();
+    let handler = LocalFileHandler::new(output_path.to_str().unwrap()).unwrap();
+    assert_eq!(handler.get_environment().api_key, "B62qaMZNwfiqT76p38ggrQ");
+    let saved_document: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&output_path).unwrap()).unwrap();
+    assert_eq!(saved_document, environment_json);
+    std::fs::remove_file(output_path).unwrap();
+}
+
+#[rstest]
+fn test_cli_diff_reports_no_differences_for_same_document() {
+    // When
+    let output = flagsmith_cli(&[
+        "diff",
+        "--base",
+        ENVIRONMENT_DOCUMENT,
+        "--other",
+        ENVIRONMENT_DOCUMENT,
+    ]);
+
+    // Then
+    assert_eq!(stdout(&output), "No differences.\n");
+}
+
+#[rstest]
+fn test_cli_diff_reports_differences_as_json(mut environment_json: serde_json::Value) {
+    // Given
+    environment_json["feature_states"][0]["enabled"] = serde_json::json!(false);
+    let other_path = std::env::temp_dir().join("flagsmith_cli_test_other_environment.json");
+    std::fs::write(&other_path, environment_json.to_string()).unwrap();
+
+    // When
+    let output = flagsmith_cli(&[
+        "diff",
+        "--base",
+        ENVIRONMENT_DOCUMENT,
+        "--other",
+        other_path.to_str().unwrap(),
+        "--format",
+        "json",
+    ]);
+
+    // Then
+    let differences: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(
+        differences,
+        serde_json::json!([{
+            "kind": "feature",
+            "name": fixtures::FEATURE_1_NAME,
+            "field": "enabled",
+            "base": true,
+            "other": false,
+        }])
+    );
+    std::fs::remove_file(other_path).unwrap();
+}
+
+#[rstest]
+fn test_cli_diff_requires_both_environments() {
+    // When
+    let output = flagsmith_cli(&["diff", "--base", ENVIRONMENT_DOCUMENT]);
+
+    // Then
+    assert!(!output.status.success());
+}
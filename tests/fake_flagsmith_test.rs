@@ -0,0 +1,159 @@
+use flagsmith::flagsmith::testing::FakeFlagsmith;
+use flagsmith::FlagProvider;
+use flagsmith_flag_engine::types::{FlagsmithValue, FlagsmithValueType};
+
+use rstest::*;
+
+mod fixtures;
+
+use fixtures::default_flag_handler;
+
+fn string_value(value: &str) -> FlagsmithValue {
+    FlagsmithValue {
+        value: value.to_string(),
+        value_type: FlagsmithValueType::String,
+    }
+}
+
+#[rstest]
+fn test_fake_flagsmith_serves_environment_and_identity_flags() {
+    // Given
+    let flagsmith = FakeFlagsmith::new();
+    flagsmith.set_environment_flag("feature_1", true, string_value("environment"));
+    flagsmith.set_environment_flag("feature_2", false, string_value("environment"));
+    flagsmith.set_identity_flag(
+        "some_identifier",
+        "feature_1",
+        false,
+        string_value("identity"),
+    );
+
+    // When
+    let environment_flags = flagsmith.get_environment_flags().unwrap();
+    let identity_flags = flagsmith
+        .get_identity_flags("some_identifier", None, None)
+        .unwrap();
+    let other_identity_flags = flagsmith
+        .get_identity_flags("other_identifier", None, None)
+        .unwrap();
+
+    // Then
+    assert!(environment_flags.is_feature_enabled("feature_1").unwrap());
+    let identity_flag = identity_flags.get_flag("feature_1").unwrap();
+    assert!(!identity_flag.enabled);
+    assert_eq!(identity_flag.value_as_string().unwrap(), "identity");
+    assert_eq!(
+        identity_flag.feature_id,
+        environment_flags.get_flag("feature_1").unwrap().feature_id
+    );
+    assert!(!identity_flags.is_feature_enabled("feature_2").unwrap());
+    assert_eq!(
+        other_identity_flags
+            .get_feature_value_as_string("feature_1")
+            .unwrap(),
+        "environment"
+    );
+}
+
+#[rstest]
+fn test_fake_flagsmith_records_flag_reads() {
+    // Given
+    let flagsmith = FakeFlagsmith::new();
+    flagsmith.set_environment_flag("feature_1", true, string_value("value"));
+    flagsmith.set_environment_flag("feature_2", true, string_value("value"));
+    let flags = flagsmith.get_environment_flags().unwrap();
+
+    // When
+    flags.is_feature_enabled("feature_1").unwrap();
+    flags.get_feature_value_as_string("feature_1").unwrap();
+
+    // Then
+    assert_eq!(flagsmith.read_flags(), vec!["feature_1", "feature_1"]);
+    assert!(flagsmith.was_read("feature_1"));
+    assert!(!flagsmith.was_read("feature_2"));
+    flagsmith.reset();
+    assert!(flagsmith.read_flags().is_empty());
+}
+
+#[rstest]
+fn test_fake_flagsmith_uses_default_flag_handler(
+    default_flag_handler: fn(&str) -> flagsmith::Flag,
+) {
+    // Given
+    let flagsmith = FakeFlagsmith::new().with_default_flag_handler(default_flag_handler);
+
+    // When
+    let flag = flagsmith
+        .get_environment_flags()
+        .unwrap()
+        .get_flag("missing_feature")
+        .unwrap();
+
+    // Then
+    assert!(flag.is_default);
+    assert_eq!(flag.value.value, fixtures::DEFAULT_FLAG_HANDLER_FLAG_VALUE);
+    assert!(flagsmith.was_read("missing_feature"));
+}
+
+#[rstest]
+fn test_fake_flagsmith_records_reads_of_unset_flags() {
+    // Given
+    let flagsmith = FakeFlagsmith::new();
+    let flags = flagsmith.get_environment_flags().unwrap();
+
+    // When
+    let result = flags.get_flag("missing_feature");
+
+    // Then
+    assert!(result.is_err());
+    assert_eq!(flagsmith.read_flags(), vec!["missing_feature"]);
+}
+
+#[rstest]
+fn test_fake_flagsmith_serves_identity_segments() {
+    // Given
+    let flagsmith = FakeFlagsmith::new();
+    flagsmith.add_identity_segment("some_identifier", "beta_users");
+    flagsmith.add_identity_segment("some_identifier", "early_adopters");
+    flagsmith.add_identity_segment("other_identifier", "beta_users");
+
+    // When
+    let segments = flagsmith
+        .get_identity_segments("some_identifier", None)
+        .unwrap();
+    let other_segments = flagsmith
+        .get_identity_segments("other_identifier", None)
+        .unwrap();
+
+    // Then
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].name, "beta_users");
+    assert_eq!(segments[0].id, other_segments[0].id);
+    assert_ne!(segments[0].id, segments[1].id);
+    assert!(flagsmith
+        .get_identity_segments("unknown_identifier", None)
+        .unwrap()
+        .is_empty());
+}
+
+fn segment_names(provider: &dyn FlagProvider, identifier: &str) -> Vec<String> {
+    provider
+        .get_identity_segments(identifier, None)
+        .unwrap()
+        .into_iter()
+        .map(|segment| segment.name)
+        .collect()
+}
+
+#[rstest]
+fn test_fake_flagsmith_can_be_used_as_flag_provider() {
+    // Given
+    let flagsmith = FakeFlagsmith::new();
+    flagsmith.add_identity_segment("some_identifier", "beta_users");
+
+    // Then
+    assert_eq!(
+        segment_names(&flagsmith, "some_identifier"),
+        vec!["beta_users"]
+    );
+}
@@ -1,8 +1,19 @@
+use flagsmith::flagsmith::environment_builder::{
+    EnvironmentBuilder, FeatureBuilder, RuleBuilder, SegmentBuilder,
+};
 use flagsmith::flagsmith::models::SDKTrait;
 use flagsmith::flagsmith::offline_handler;
-use flagsmith::{Flagsmith, FlagsmithOptions};
+use flagsmith::flagsmith::pool::FlagsmithPool;
+use flagsmith::flagsmith::request_options::{EvaluationMode, RequestOptions};
+use flagsmith::flagsmith::status::ClientMode;
+use flagsmith::flagsmith::transport::{
+    ClientCertificate, HttpRequest, HttpResponse, HttpTransport, ProxyOptions,
+};
+use flagsmith::{FlagProvider, Flagsmith, FlagsmithOptions, IdentityContext};
 use flagsmith_flag_engine::identities::Trait;
+use flagsmith_flag_engine::segments::constants::EQUAL;
 use flagsmith_flag_engine::types::{FlagsmithValue, FlagsmithValueType};
+use http::header::{HeaderName, HeaderValue};
 
 use httpmock::prelude::*;
 use rstest::*;
@@ -824,3 +835,1521 @@ fn test_get_identity_segments_filters_identity_override_segments(local_eval_flag
     );
     assert_eq!(segments[0].id, 1, "Should have correct segment ID");
 }
+
+#[rstest]
+fn test_get_environment_flags_uses_cache_when_enabled(
+    mock_server: MockServer,
+    flags_json: serde_json::Value,
+) {
+    // Given
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/flags/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY);
+        then.status(200).json_body(flags_json);
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        enable_environment_flags_cache: true,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    flagsmith.get_environment_flags().unwrap();
+    let all_flags = flagsmith.get_environment_flags().unwrap().all_flags();
+
+    // Then
+    assert_eq!(all_flags.len(), 1);
+    assert_eq!(all_flags[0].feature_name, fixtures::FEATURE_1_NAME);
+    // only the initial fetch should hit the API
+    api_mock.assert_hits(1);
+}
+
+#[rstest]
+fn test_environment_flags_cache_is_refreshed_in_background(
+    mock_server: MockServer,
+    flags_json: serde_json::Value,
+) {
+    // Given
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/flags/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY);
+        then.status(200).json_body(flags_json);
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        enable_environment_flags_cache: true,
+        environment_refresh_interval_mills: 100,
+        ..Default::default()
+    };
+
+    // When
+    let _flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    std::thread::sleep(std::time::Duration::from_millis(250));
+
+    // Then
+    // one call on initialization and 2 for each subsequent refresh
+    api_mock.assert_hits(3);
+}
+
+#[rstest]
+fn test_environment_flags_cache_serves_last_good_response_on_error(
+    mock_server: MockServer,
+    flags_json: serde_json::Value,
+) {
+    // Given
+    let mut api_mock = mock_server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/flags/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY);
+        then.status(200).json_body(flags_json);
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        enable_environment_flags_cache: true,
+        environment_refresh_interval_mills: 50,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    api_mock.delete();
+    let failing_mock = mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/flags/");
+        then.status(502);
+    });
+    std::thread::sleep(std::time::Duration::from_millis(150));
+    let flags = flagsmith.get_environment_flags().unwrap();
+
+    // Then
+    assert!(failing_mock.hits() > 0);
+    assert_eq!(
+        flags
+            .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+            .unwrap(),
+        fixtures::FEATURE_1_STR_VALUE
+    );
+}
+
+#[rstest]
+#[should_panic(expected = "enable_environment_flags_cache cannot be used with local evaluation")]
+fn test_flagsmith_panics_if_environment_flags_cache_is_used_with_local_evaluation() {
+    let flagsmith_options = FlagsmithOptions {
+        enable_local_evaluation: true,
+        enable_environment_flags_cache: true,
+        ..Default::default()
+    };
+    Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+}
+
+#[rstest]
+fn test_get_identities_flags_uses_local_environment_when_available(
+    local_eval_flagsmith: Flagsmith,
+) {
+    // Given
+    let traits = vec![SDKTrait::new(
+        "foo".to_string(),
+        FlagsmithValue {
+            value: "bar".to_string(),
+            value_type: FlagsmithValueType::String,
+        },
+    )];
+    let identities = vec![("identity_1", vec![]), ("identity_2", traits.clone())];
+
+    // When
+    let identities_flags = local_eval_flagsmith
+        .get_identities_flags(&identities)
+        .unwrap();
+
+    // Then
+    assert_eq!(identities_flags.len(), 2);
+    let expected_flags = local_eval_flagsmith
+        .get_identity_flags("identity_2", Some(traits), None)
+        .unwrap();
+    assert_eq!(
+        identities_flags[1]
+            .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+            .unwrap(),
+        expected_flags
+            .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+            .unwrap()
+    );
+}
+
+#[rstest]
+fn test_get_identities_flags_evaluates_in_parallel_and_preserves_order(
+    mut environment_json: serde_json::Value,
+) {
+    // Given - the identity override of the fixture targets feature_1
+    environment_json["identity_overrides"][0]["identity_features"][0]["feature"]["name"] =
+        serde_json::json!(fixtures::FEATURE_1_NAME);
+    let path = std::env::temp_dir().join("flagsmith_bulk_evaluation_environment.json");
+    std::fs::write(&path, environment_json.to_string()).unwrap();
+    let handler = offline_handler::LocalFileHandler::new(path.to_str().unwrap()).unwrap();
+    let flagsmith_options = FlagsmithOptions {
+        offline_handler: Some(Box::new(handler)),
+        bulk_evaluation_parallelism: 3,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let mut identifiers: Vec<String> = (0..10).map(|i| format!("identity_{}", i)).collect();
+    identifiers[7] = "overridden-id".to_string();
+    let identities: Vec<(&str, Vec<SDKTrait>)> = identifiers
+        .iter()
+        .map(|identifier| (identifier.as_str(), vec![]))
+        .collect();
+
+    // When
+    let identities_flags = flagsmith.get_identities_flags(&identities).unwrap();
+
+    // Then
+    assert_eq!(identities_flags.len(), identities.len());
+    for (index, flags) in identities_flags.iter().enumerate() {
+        let (expected_value, expected_enabled) = if index == 7 {
+            ("some-overridden-value", false)
+        } else {
+            (fixtures::FEATURE_1_STR_VALUE, true)
+        };
+        assert_eq!(
+            flags
+                .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+                .unwrap(),
+            expected_value
+        );
+        assert_eq!(
+            flags.is_feature_enabled(fixtures::FEATURE_1_NAME).unwrap(),
+            expected_enabled
+        );
+    }
+}
+
+#[rstest]
+fn test_get_identities_flags_calls_identities_api_per_identity_when_no_local_environment(
+    mock_server: MockServer,
+    identities_json: serde_json::Value,
+) {
+    // Given
+    let first_identity_mock = mock_server.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/identities/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY)
+            .json_body(serde_json::json!({
+                "identifier": "identity_1",
+                "traits": [],
+                "transient": false,
+            }));
+        then.status(200).json_body(identities_json.clone());
+    });
+    let second_identity_mock = mock_server.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/identities/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY)
+            .json_body(serde_json::json!({
+                "identifier": "identity_2",
+                "traits": [{"trait_key": "foo", "trait_value": "bar", "transient": false}],
+                "transient": false,
+            }));
+        then.status(200).json_body(identities_json);
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let traits = vec![SDKTrait::new(
+        "foo".to_string(),
+        FlagsmithValue {
+            value: "bar".to_string(),
+            value_type: FlagsmithValueType::String,
+        },
+    )];
+
+    // When
+    let identities_flags = flagsmith
+        .get_identities_flags(&[("identity_1", vec![]), ("identity_2", traits)])
+        .unwrap();
+
+    // Then
+    assert_eq!(identities_flags.len(), 2);
+    assert_eq!(
+        identities_flags[1]
+            .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+            .unwrap(),
+        fixtures::FEATURE_1_STR_VALUE
+    );
+    first_identity_mock.assert();
+    second_identity_mock.assert();
+}
+
+#[rstest]
+fn test_get_identities_flags_sends_identities_requests_in_parallel_and_preserves_order(
+    mock_server: MockServer,
+    identities_json: serde_json::Value,
+) {
+    // Given - each identity gets its identifier as the value of feature_1
+    let identifiers: Vec<String> = (0..6).map(|i| format!("identity_{}", i)).collect();
+    let mocks: Vec<_> = identifiers
+        .iter()
+        .map(|identifier| {
+            let mut response = identities_json.clone();
+            response["flags"][0]["feature_state_value"] = serde_json::json!(identifier);
+            mock_server.mock(|when, then| {
+                when.method(POST)
+                    .path("/api/v1/identities/")
+                    .json_body_partial(serde_json::json!({ "identifier": identifier }).to_string());
+                then.status(200).json_body(response);
+            })
+        })
+        .collect();
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        bulk_evaluation_parallelism: 3,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let identities: Vec<(&str, Vec<SDKTrait>)> = identifiers
+        .iter()
+        .map(|identifier| (identifier.as_str(), vec![]))
+        .collect();
+
+    // When
+    let identities_flags = flagsmith.get_identities_flags(&identities).unwrap();
+
+    // Then
+    let values: Vec<String> = identities_flags
+        .iter()
+        .map(|flags| {
+            flags
+                .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(values, identifiers);
+    for mock in mocks {
+        mock.assert();
+    }
+}
+
+#[rstest]
+fn test_default_flags_are_used_if_api_error_and_default_flag_handler_given_for_identities(
+    mock_server: MockServer,
+    default_flag_handler: fn(&str) -> flagsmith::Flag,
+) {
+    // Given
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(POST).path("/api/v1/identities/");
+        then.status(502);
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        default_flag_handler: Some(default_flag_handler),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    let identities_flags = flagsmith
+        .get_identities_flags(&[("identity_1", vec![]), ("identity_2", vec![])])
+        .unwrap();
+
+    // Then
+    assert_eq!(identities_flags.len(), 2);
+    let flag = identities_flags[0]
+        .get_flag(fixtures::FEATURE_1_NAME)
+        .unwrap();
+    assert!(flag.is_default);
+    api_mock.assert_hits(2);
+}
+
+#[rstest]
+fn test_get_identity_segments_returns_segment_definition(local_eval_flagsmith: Flagsmith) {
+    // Given
+    let traits = vec![Trait {
+        trait_key: "foo".to_string(),
+        trait_value: FlagsmithValue {
+            value: "bar".to_string(),
+            value_type: FlagsmithValueType::String,
+        },
+    }];
+
+    // When
+    let segments = local_eval_flagsmith
+        .get_identity_segments("some_identifier", Some(traits))
+        .unwrap();
+
+    // Then - rules are lifted from fixtures::environment_json
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].rules.len(), 1);
+    assert_eq!(segments[0].rules[0].segment_rule_type, "ALL");
+    let condition = &segments[0].rules[0].rules[0].conditions[0];
+    assert_eq!(condition.operator, "EQUAL");
+    assert_eq!(condition.property.as_deref(), Some("foo"));
+    assert_eq!(condition.value.as_deref(), Some("bar"));
+}
+
+#[rstest]
+fn test_get_identity_segments_with_overrides_includes_identity_overrides(
+    local_eval_flagsmith: Flagsmith,
+) {
+    // Given - environment fixture includes identity override for "overridden-id"
+    let identifier = "overridden-id";
+
+    // When
+    let segments = local_eval_flagsmith
+        .get_identity_segments_with_overrides(identifier, None)
+        .unwrap();
+
+    // Then
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].name, "identity_overrides");
+    assert_eq!(segments[0].feature_states.len(), 1);
+    assert_eq!(segments[0].feature_states[0].feature.name, "some_feature");
+    assert_eq!(
+        segments[0].rules[0].conditions[0].value.as_deref(),
+        Some(identifier)
+    );
+}
+
+#[rstest]
+fn test_get_identity_segments_with_overrides_without_identity_overrides(
+    local_eval_flagsmith: Flagsmith,
+) {
+    // When
+    let segments = local_eval_flagsmith
+        .get_identity_segments_with_overrides("some_identifier", None)
+        .unwrap();
+
+    // Then
+    assert_eq!(segments.len(), 0);
+}
+
+#[rstest]
+fn test_explain_identity_traces_identity_overrides(local_eval_flagsmith: Flagsmith) {
+    // When
+    let explanation = local_eval_flagsmith
+        .explain_identity("overridden-id", None)
+        .unwrap();
+
+    // Then
+    assert_eq!(explanation.identifier, "overridden-id");
+    let identity_overrides = explanation
+        .segments
+        .iter()
+        .find(|segment| segment.name == "identity_overrides")
+        .unwrap();
+    assert!(identity_overrides.matched);
+    let segment = explanation
+        .segments
+        .iter()
+        .find(|segment| segment.name == "Test Segment")
+        .unwrap();
+    assert!(!segment.matched);
+    assert_eq!(explanation.flags.len(), 1);
+    assert_eq!(explanation.flags[0].feature_name, fixtures::FEATURE_1_NAME);
+}
+
+#[rstest]
+fn test_explain_identity_requires_local_evaluation(mock_server: MockServer) {
+    // Given
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    let err = flagsmith
+        .explain_identity("some_identifier", None)
+        .unwrap_err();
+
+    // Then
+    assert_eq!(err.kind, flagsmith::error::ErrorKind::FlagsmithClientError);
+}
+
+#[rstest]
+fn test_environment_listeners_are_notified_of_flag_changes(
+    mock_server: MockServer,
+    environment_json: serde_json::Value,
+) {
+    // Given
+    let mut api_mock = mock_server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/environment-document/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY);
+        then.status(200).json_body(environment_json.clone());
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        ..Default::default()
+    };
+    let mut flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    flagsmith.update_environment().unwrap();
+
+    let diffs = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let flag_changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let diffs_clone = std::sync::Arc::clone(&diffs);
+    flagsmith.on_environment_updated(move |diff| diffs_clone.lock().unwrap().push(diff.clone()));
+    let flag_changes_clone = std::sync::Arc::clone(&flag_changes);
+    flagsmith.on_flag_changed(fixtures::FEATURE_1_NAME, move |change| {
+        flag_changes_clone.lock().unwrap().push(change.clone())
+    });
+
+    // When - the same environment is fetched again, then feature_1 gets disabled
+    flagsmith.update_environment().unwrap();
+    api_mock.delete();
+    let mut disabled_environment_json = environment_json.clone();
+    disabled_environment_json["feature_states"][0]["enabled"] = serde_json::json!(false);
+    mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/environment-document/");
+        then.status(200).json_body(disabled_environment_json);
+    });
+    flagsmith.update_environment().unwrap();
+
+    // Then
+    let diffs = diffs.lock().unwrap();
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].changes.len(), 1);
+    let flag_changes = flag_changes.lock().unwrap();
+    assert_eq!(flag_changes.len(), 1);
+    assert!(flag_changes[0].enabled_changed());
+    assert!(!flag_changes[0].current.as_ref().unwrap().enabled);
+}
+
+#[rstest]
+fn test_status_reports_failed_local_evaluation_refreshes(mock_server: MockServer) {
+    // Given
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/environment-document/");
+        then.status(502).body("bad gateway");
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        enable_local_evaluation: true,
+        ..Default::default()
+    };
+    let mut flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    let _ = flagsmith.update_environment();
+    let status = flagsmith.status();
+
+    // Then
+    api_mock.assert_hits(2);
+    assert_eq!(status.mode, ClientMode::LocalEvaluation);
+    assert!(!status.is_ready());
+    assert!(status.last_successful_refresh.is_none());
+    assert_eq!(status.consecutive_failures, 2);
+    assert!(status.last_error.unwrap().contains("bad gateway"));
+}
+
+#[rstest]
+fn test_status_reports_environment_updated_at(
+    mock_server: MockServer,
+    environment_json: serde_json::Value,
+) {
+    // Given
+    let mut environment_json = environment_json;
+    environment_json["updated_at"] = serde_json::json!("2023-07-14T16:12:00.000000Z");
+    mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/environment-document/");
+        then.status(200).json_body(environment_json);
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        enable_local_evaluation: true,
+        ..Default::default()
+    };
+
+    // When
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let status = flagsmith.status();
+
+    // Then
+    assert!(status.is_ready());
+    assert_eq!(status.consecutive_failures, 0);
+    assert!(status.last_error.is_none());
+    assert_eq!(
+        status.environment_updated_at.unwrap().to_rfc3339(),
+        "2023-07-14T16:12:00+00:00"
+    );
+}
+
+#[rstest]
+fn test_status_mode(mock_server: MockServer, mut environment_json: serde_json::Value) {
+    // Given
+    environment_json["updated_at"] = serde_json::json!("2023-07-14T16:12:00Z");
+    let path = std::env::temp_dir().join("flagsmith_status_mode_environment.json");
+    std::fs::write(&path, environment_json.to_string()).unwrap();
+    let url = mock_server.url("/api/v1/");
+    let remote_flagsmith = Flagsmith::new(
+        ENVIRONMENT_KEY.to_string(),
+        FlagsmithOptions {
+            api_url: url,
+            ..Default::default()
+        },
+    );
+    let offline_flagsmith = Flagsmith::new(
+        ENVIRONMENT_KEY.to_string(),
+        FlagsmithOptions {
+            offline_handler: Some(Box::new(
+                offline_handler::LocalFileHandler::new(path.to_str().unwrap()).unwrap(),
+            )),
+            ..Default::default()
+        },
+    );
+
+    // Then
+    let remote_status = remote_flagsmith.status();
+    assert_eq!(remote_status.mode, ClientMode::Remote);
+    assert!(remote_status.is_ready());
+    let offline_status = offline_flagsmith.status();
+    assert_eq!(offline_status.mode, ClientMode::Offline);
+    assert!(offline_status.is_ready());
+    assert_eq!(
+        offline_status.environment_updated_at.unwrap().to_rfc3339(),
+        "2023-07-14T16:12:00+00:00"
+    );
+}
+
+#[rstest]
+fn test_wait_for_environment_returns_once_environment_is_loaded(
+    mock_server: MockServer,
+    environment_json: serde_json::Value,
+) {
+    // Given
+    let mut failing_mock = mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/environment-document/");
+        then.status(502);
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        enable_local_evaluation: true,
+        environment_refresh_interval_mills: 50,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    assert!(!flagsmith.status().is_ready());
+
+    // When
+    failing_mock.delete();
+    mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/environment-document/");
+        then.status(200).json_body(environment_json);
+    });
+    let result = flagsmith.wait_for_environment(std::time::Duration::from_secs(5));
+
+    // Then
+    assert!(result.is_ok());
+    assert!(flagsmith.status().is_ready());
+}
+
+#[rstest]
+fn test_wait_for_environment_times_out(mock_server: MockServer) {
+    // Given
+    mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/environment-document/");
+        then.status(502).body("bad gateway");
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        enable_local_evaluation: true,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    let err = flagsmith
+        .wait_for_environment(std::time::Duration::from_millis(50))
+        .unwrap_err();
+
+    // Then
+    assert_eq!(err.kind, flagsmith::error::ErrorKind::FlagsmithClientError);
+    assert!(err.msg.contains("bad gateway"));
+}
+
+#[rstest]
+fn test_strict_local_evaluation_does_not_fall_back_to_api(
+    mock_server: MockServer,
+    flags_json: serde_json::Value,
+    identities_json: serde_json::Value,
+) {
+    // Given
+    mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/environment-document/");
+        then.status(502);
+    });
+    let flags_mock = mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/flags/");
+        then.status(200).json_body(flags_json);
+    });
+    let identities_mock = mock_server.mock(|when, then| {
+        when.method(POST).path("/api/v1/identities/");
+        then.status(200).json_body(identities_json);
+    });
+    let url = mock_server.url("/api/v1/");
+    let flagsmith_options = FlagsmithOptions {
+        api_url: url,
+        enable_local_evaluation: true,
+        strict_local_evaluation: true,
+        default_flag_handler: Some(default_flag_handler()),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    let environment_err = flagsmith.get_environment_flags().err().unwrap();
+    let identity_err = flagsmith
+        .get_identity_flags("some_identifier", None, None)
+        .err()
+        .unwrap();
+
+    // Then
+    flags_mock.assert_hits(0);
+    identities_mock.assert_hits(0);
+    assert_eq!(
+        environment_err.kind,
+        flagsmith::error::ErrorKind::FlagsmithClientError
+    );
+    assert_eq!(
+        identity_err.kind,
+        flagsmith::error::ErrorKind::FlagsmithClientError
+    );
+}
+
+#[rstest]
+#[should_panic(expected = "strict_local_evaluation requires enable_local_evaluation")]
+fn test_flagsmith_panics_if_strict_local_evaluation_is_used_without_local_evaluation() {
+    let flagsmith_options = FlagsmithOptions {
+        strict_local_evaluation: true,
+        ..Default::default()
+    };
+    Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+}
+
+#[rstest]
+fn test_local_overrides_are_applied_to_local_evaluation(local_eval_flagsmith: Flagsmith) {
+    // Given
+    let value = FlagsmithValue {
+        value: "overridden".to_string(),
+        value_type: FlagsmithValueType::String,
+    };
+
+    // When
+    local_eval_flagsmith.set_override(fixtures::FEATURE_1_NAME, false, value);
+    let environment_flags = local_eval_flagsmith.get_environment_flags().unwrap();
+    let identity_flags = local_eval_flagsmith
+        .get_identity_flags("some_identifier", None, None)
+        .unwrap();
+    local_eval_flagsmith.clear_override(fixtures::FEATURE_1_NAME);
+    let cleared_flags = local_eval_flagsmith.get_environment_flags().unwrap();
+
+    // Then
+    for flags in [environment_flags, identity_flags] {
+        assert!(flags.is_overridden(fixtures::FEATURE_1_NAME));
+        let flag = flags.get_flag(fixtures::FEATURE_1_NAME).unwrap();
+        assert!(!flag.enabled);
+        assert_eq!(flag.value.value, "overridden");
+        assert_eq!(flag.feature_id, fixtures::FEATURE_1_ID);
+    }
+    assert!(!cleared_flags.is_overridden(fixtures::FEATURE_1_NAME));
+    assert_eq!(
+        cleared_flags
+            .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+            .unwrap(),
+        fixtures::FEATURE_1_STR_VALUE
+    );
+}
+
+#[rstest]
+fn test_local_overrides_file_is_applied_to_remote_evaluation(
+    mock_server: MockServer,
+    identities_json: serde_json::Value,
+) {
+    // Given
+    mock_server.mock(|when, then| {
+        when.method(POST).path("/api/v1/identities/");
+        then.status(200).json_body(identities_json);
+    });
+    let overrides_path = std::env::temp_dir().join("flagsmith_integration_overrides.json");
+    std::fs::write(
+        &overrides_path,
+        r#"{"feature_1": {"enabled": false, "value": 42}, "local_only": {}}"#,
+    )
+    .unwrap();
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        local_overrides_path: Some(overrides_path.to_str().unwrap().to_string()),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    std::fs::remove_file(overrides_path).unwrap();
+
+    // When
+    let flags = flagsmith
+        .get_identity_flags("some_identifier", None, None)
+        .unwrap();
+
+    // Then
+    let feature_1 = flags.get_flag(fixtures::FEATURE_1_NAME).unwrap();
+    assert!(flags.is_overridden(fixtures::FEATURE_1_NAME));
+    assert!(!feature_1.enabled);
+    assert_eq!(feature_1.value_as_i64(), Some(42));
+    assert!(flags.is_feature_enabled("local_only").unwrap());
+}
+
+#[rstest]
+fn test_overridden_flags_are_not_tracked_by_analytics(
+    mock_server: MockServer,
+    environment_json: serde_json::Value,
+) {
+    // Given
+    mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/environment-document/");
+        then.status(200).json_body(environment_json);
+    });
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        enable_local_evaluation: true,
+        enable_analytics: true,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    flagsmith.set_override(fixtures::FEATURE_1_NAME, true, FlagsmithValue::default());
+
+    // When
+    let flags = flagsmith.get_environment_flags().unwrap();
+    flags.get_flag(fixtures::FEATURE_1_NAME).unwrap();
+    flagsmith.clear_overrides();
+    let flags = flagsmith.get_environment_flags().unwrap();
+    flags.get_flag(fixtures::FEATURE_1_NAME).unwrap();
+
+    // Then
+    assert_eq!(flagsmith.status().analytics_queue_depth, 1);
+}
+
+#[rstest]
+fn test_environment_builder_document_is_evaluated_offline() {
+    // Given
+    let environment = EnvironmentBuilder::new(ENVIRONMENT_KEY)
+        .feature(FeatureBuilder::new("banner").enabled(true).value("default"))
+        .feature(
+            FeatureBuilder::new("colour")
+                .value("red")
+                .variant("blue", 100.0),
+        )
+        .segment(
+            SegmentBuilder::new("premium")
+                .rule(RuleBuilder::all().condition("plan", EQUAL, "premium"))
+                .feature_override("banner", true, "premium", 1),
+        )
+        .identity_override("overridden-id", "banner", false, "hidden")
+        .build()
+        .unwrap();
+    let flagsmith_options = FlagsmithOptions {
+        offline_handler: Some(Box::new(offline_handler::InMemoryHandler::new(environment))),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let premium_traits = vec![SDKTrait::new(
+        "plan".to_string(),
+        FlagsmithValue {
+            value: "premium".to_string(),
+            value_type: FlagsmithValueType::String,
+        },
+    )];
+
+    // When
+    let environment_flags = flagsmith.get_environment_flags().unwrap();
+    let premium_flags = flagsmith
+        .get_identity_flags("premium-id", Some(premium_traits), None)
+        .unwrap();
+    let overridden_flags = flagsmith
+        .get_identity_flags("overridden-id", None, None)
+        .unwrap();
+
+    // Then
+    assert_eq!(
+        environment_flags
+            .get_feature_value_as_string("banner")
+            .unwrap(),
+        "default"
+    );
+    assert_eq!(
+        premium_flags.get_feature_value_as_string("banner").unwrap(),
+        "premium"
+    );
+    assert_eq!(
+        premium_flags.get_feature_value_as_string("colour").unwrap(),
+        "blue"
+    );
+    assert!(!overridden_flags.is_feature_enabled("banner").unwrap());
+    assert_eq!(
+        overridden_flags
+            .get_feature_value_as_string("banner")
+            .unwrap(),
+        "hidden"
+    );
+}
+
+#[rstest]
+fn test_environment_builder_document_can_be_loaded_from_file() {
+    // Given
+    let document = EnvironmentBuilder::new(ENVIRONMENT_KEY)
+        .feature(FeatureBuilder::new("feature_1").enabled(true).value(10))
+        .to_json()
+        .unwrap();
+    let path = std::env::temp_dir().join("flagsmith_built_environment.json");
+    std::fs::write(&path, document.to_string()).unwrap();
+
+    // When
+    let handler = offline_handler::LocalFileHandler::new(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    let flagsmith = Flagsmith::new(
+        ENVIRONMENT_KEY.to_string(),
+        FlagsmithOptions {
+            offline_handler: Some(Box::new(handler)),
+            ..Default::default()
+        },
+    );
+
+    // Then
+    let flag = flagsmith
+        .get_environment_flags()
+        .unwrap()
+        .get_flag("feature_1")
+        .unwrap();
+    assert!(flag.enabled);
+    assert_eq!(flag.value_as_i64(), Some(10));
+}
+
+fn is_feature_1_enabled_for(provider: &impl FlagProvider, identifier: &str) -> bool {
+    provider
+        .get_identity_flags(identifier, None, None)
+        .unwrap()
+        .is_feature_enabled(fixtures::FEATURE_1_NAME)
+        .unwrap()
+}
+
+#[rstest]
+fn test_flagsmith_can_be_used_as_flag_provider(local_eval_flagsmith: Flagsmith) {
+    // Given
+    let shared_flagsmith = std::sync::Arc::new(local_eval_flagsmith);
+    let boxed_provider: Box<dyn FlagProvider> = Box::new(std::sync::Arc::clone(&shared_flagsmith));
+
+    // Then
+    assert!(is_feature_1_enabled_for(
+        &shared_flagsmith,
+        "some_identifier"
+    ));
+    assert!(boxed_provider
+        .get_environment_flags()
+        .unwrap()
+        .is_feature_enabled(fixtures::FEATURE_1_NAME)
+        .unwrap());
+    assert_eq!(
+        boxed_provider
+            .get_identity_segments("some_identifier", None)
+            .unwrap()
+            .len(),
+        0
+    );
+}
+
+#[rstest]
+fn test_pool_routes_identity_flags_to_environment(
+    mock_server: MockServer,
+    identities_json: serde_json::Value,
+) {
+    // Given
+    let other_environment_key = "other_environment_key";
+    let mut other_identities_json = identities_json.clone();
+    other_identities_json["flags"][0]["feature_state_value"] = serde_json::json!("other_value");
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/identities/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY);
+        then.status(200).json_body(identities_json);
+    });
+    let other_api_mock = mock_server.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/identities/")
+            .header("X-Environment-Key", other_environment_key);
+        then.status(200).json_body(other_identities_json);
+    });
+    let pool = FlagsmithPool::new(FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        ..Default::default()
+    });
+    pool.add_environment(ENVIRONMENT_KEY).unwrap();
+    pool.add_environment(other_environment_key).unwrap();
+
+    // When
+    let flags = pool
+        .get_identity_flags(ENVIRONMENT_KEY, "test_identity", None, None)
+        .unwrap();
+    let other_flags = pool
+        .get_identity_flags(other_environment_key, "test_identity", None, None)
+        .unwrap();
+
+    // Then
+    assert_eq!(
+        flags
+            .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+            .unwrap(),
+        fixtures::FEATURE_1_STR_VALUE
+    );
+    assert_eq!(
+        other_flags
+            .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+            .unwrap(),
+        "other_value"
+    );
+    api_mock.assert();
+    other_api_mock.assert();
+}
+
+#[rstest]
+fn test_pool_refreshes_every_environment(
+    mock_server: MockServer,
+    environment_json: serde_json::Value,
+) {
+    // Given
+    let other_environment_key = "ser.other_environment_key";
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/environment-document/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY);
+        then.status(200).json_body(environment_json.clone());
+    });
+    let other_api_mock = mock_server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/environment-document/")
+            .header("X-Environment-Key", other_environment_key);
+        then.status(200).json_body(environment_json);
+    });
+    let pool = FlagsmithPool::new(FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        enable_local_evaluation: true,
+        environment_refresh_interval_mills: 100,
+        ..Default::default()
+    });
+
+    // When
+    pool.add_environment(ENVIRONMENT_KEY).unwrap();
+    pool.add_environment(other_environment_key).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(350));
+
+    // Then
+    assert!(api_mock.hits() >= 3);
+    assert!(other_api_mock.hits() >= 3);
+    let flags = pool.get_environment_flags(other_environment_key).unwrap();
+    assert!(flags.is_feature_enabled(fixtures::FEATURE_1_NAME).unwrap());
+    assert_eq!(
+        pool.get(other_environment_key).unwrap().status().mode,
+        ClientMode::LocalEvaluation
+    );
+}
+
+#[rstest]
+fn test_pool_returns_error_for_invalid_environment_key(mock_server: MockServer) {
+    // Given
+    let pool = FlagsmithPool::new(FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        enable_local_evaluation: true,
+        ..Default::default()
+    });
+
+    // When
+    let client_side_key_err = pool.add_environment("client_side_key").err().unwrap();
+    let malformed_key_err = pool.add_environment("ser.key\n").err().unwrap();
+
+    // Then
+    assert_eq!(
+        client_side_key_err.kind,
+        flagsmith::error::ErrorKind::FlagsmithClientError
+    );
+    assert_eq!(
+        malformed_key_err.kind,
+        flagsmith::error::ErrorKind::FlagsmithClientError
+    );
+    assert!(pool.environment_keys().is_empty());
+}
+
+#[rstest]
+#[should_panic(expected = "strict_local_evaluation requires enable_local_evaluation")]
+fn test_pool_panics_on_invalid_options() {
+    FlagsmithPool::new(FlagsmithOptions {
+        strict_local_evaluation: true,
+        ..Default::default()
+    });
+}
+
+#[rstest]
+fn test_pool_returns_error_if_local_overrides_cannot_be_read(mock_server: MockServer) {
+    // Given
+    let pool = FlagsmithPool::new(FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        local_overrides_path: Some("tests/fixtures/missing_overrides.json".to_string()),
+        ..Default::default()
+    });
+
+    // When
+    let err = pool.add_environment(ENVIRONMENT_KEY).err().unwrap();
+
+    // Then - the pool is left usable
+    assert_eq!(err.kind, flagsmith::error::ErrorKind::FlagsmithClientError);
+    assert!(pool.environment_keys().is_empty());
+    assert!(pool.get(ENVIRONMENT_KEY).is_none());
+}
+
+#[rstest]
+fn test_pool_returns_error_for_unknown_environment(mock_server: MockServer) {
+    // Given
+    let pool = FlagsmithPool::new(FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        ..Default::default()
+    });
+    pool.add_environment(ENVIRONMENT_KEY).unwrap();
+    assert!(pool.remove_environment(ENVIRONMENT_KEY));
+
+    // When
+    let err = pool
+        .get_identity_flags(ENVIRONMENT_KEY, "test_identity", None, None)
+        .err()
+        .unwrap();
+
+    // Then
+    assert_eq!(err.kind, flagsmith::error::ErrorKind::FlagsmithClientError);
+    assert!(pool.environment_keys().is_empty());
+}
+
+#[rstest]
+fn test_get_environment_document_json_returns_document_as_served(
+    local_eval_flagsmith: Flagsmith,
+    environment_json: serde_json::Value,
+) {
+    // When
+    let environment_document = local_eval_flagsmith
+        .get_environment_document_json()
+        .unwrap();
+
+    // Then
+    assert_eq!(*environment_document, environment_json);
+}
+
+#[rstest]
+fn test_get_identities_with_overrides_lists_overridden_identities(local_eval_flagsmith: Flagsmith) {
+    // When
+    let identifiers = local_eval_flagsmith
+        .get_identities_with_overrides("some_feature")
+        .unwrap();
+    let not_overridden = local_eval_flagsmith
+        .get_identities_with_overrides(fixtures::FEATURE_1_NAME)
+        .unwrap();
+
+    // Then
+    assert_eq!(identifiers, vec!["overridden-id"]);
+    assert!(not_overridden.is_empty());
+}
+
+#[rstest]
+fn test_get_identities_with_overrides_requires_local_evaluation(mock_server: MockServer) {
+    // Given
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    let err = flagsmith
+        .get_identities_with_overrides("some_feature")
+        .err()
+        .unwrap();
+
+    // Then
+    assert_eq!(err.kind, flagsmith::error::ErrorKind::FlagsmithClientError);
+}
+
+#[rstest]
+fn test_get_identity_flags_for_sends_identity_context_to_api(
+    mock_server: MockServer,
+    identities_json: serde_json::Value,
+) {
+    // Given
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/identities/")
+            .json_body(serde_json::json!({
+                "identifier": "test_identity",
+                "traits": [{"trait_key": "age", "trait_value": 42, "transient": false}],
+                "transient": true,
+            }));
+        then.status(200).json_body(identities_json);
+    });
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let identity = IdentityContext::new("test_identity")
+        .with_trait("age", 42)
+        .transient(true);
+
+    // When
+    let flags = flagsmith.get_identity_flags_for(identity).unwrap();
+
+    // Then
+    api_mock.assert();
+    assert!(flags.is_feature_enabled(fixtures::FEATURE_1_NAME).unwrap());
+}
+
+#[rstest]
+fn test_identity_context_is_accepted_by_every_identity_api(local_eval_flagsmith: Flagsmith) {
+    // Given
+    let identity = IdentityContext::new("some_identity")
+        .with_trait("foo", "bar")
+        .with_override(
+            fixtures::FEATURE_1_NAME,
+            false,
+            FlagsmithValue {
+                value: "forced".to_string(),
+                value_type: FlagsmithValueType::String,
+            },
+        );
+
+    // When
+    let flags = local_eval_flagsmith
+        .get_identity_flags_for(identity.clone())
+        .unwrap();
+    let identities_flags = local_eval_flagsmith
+        .get_identities_flags_for(&[identity.clone(), "other_identity".into()])
+        .unwrap();
+    let segments = local_eval_flagsmith
+        .get_identity_segments_for(identity)
+        .unwrap();
+
+    // Then
+    let flag = flags.get_flag(fixtures::FEATURE_1_NAME).unwrap();
+    assert!(!flag.enabled);
+    assert_eq!(flag.value_as_string().unwrap(), "forced");
+    assert_eq!(
+        identities_flags[0]
+            .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+            .unwrap(),
+        "forced"
+    );
+    assert_eq!(
+        identities_flags[1]
+            .get_feature_value_as_string(fixtures::FEATURE_1_NAME)
+            .unwrap(),
+        fixtures::FEATURE_1_STR_VALUE
+    );
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].name, "Test Segment");
+}
+
+#[rstest]
+fn test_request_options_add_headers_to_the_request(
+    mock_server: MockServer,
+    flags_json: serde_json::Value,
+) {
+    // Given
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/flags/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY)
+            .header("traceparent", "00-trace-span-01");
+        then.status(200).json_body(flags_json);
+    });
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let options = RequestOptions::default().with_header(
+        HeaderName::from_static("traceparent"),
+        HeaderValue::from_static("00-trace-span-01"),
+    );
+
+    // When
+    let flags = flagsmith
+        .with_request_options(options)
+        .get_environment_flags()
+        .unwrap();
+
+    // Then
+    api_mock.assert();
+    assert!(flags.is_feature_enabled(fixtures::FEATURE_1_NAME).unwrap());
+}
+
+#[rstest]
+fn test_request_options_timeout_overrides_client_timeout(
+    mock_server: MockServer,
+    identities_json: serde_json::Value,
+) {
+    // Given
+    mock_server.mock(|when, then| {
+        when.method(POST).path("/api/v1/identities/");
+        then.status(200)
+            .delay(std::time::Duration::from_millis(500))
+            .json_body(identities_json);
+    });
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let options = RequestOptions::default().with_timeout(std::time::Duration::from_millis(50));
+
+    // When
+    let result = flagsmith
+        .with_request_options(options)
+        .get_identity_flags_for("test_identity");
+
+    // Then
+    assert_eq!(
+        result.err().unwrap().kind,
+        flagsmith::error::ErrorKind::FlagsmithAPIError
+    );
+    assert!(flagsmith.get_identity_flags_for("test_identity").is_ok());
+}
+
+#[rstest]
+fn test_request_options_force_remote_evaluation_in_local_evaluation_mode(
+    mock_server: MockServer,
+    mut environment_json: serde_json::Value,
+    flags_json: serde_json::Value,
+) {
+    // Given
+    environment_json["feature_states"][0]["enabled"] = serde_json::json!(false);
+    mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/environment-document/");
+        then.status(200).json_body(environment_json);
+    });
+    let flags_mock = mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/flags/");
+        then.status(200).json_body(flags_json);
+    });
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        enable_local_evaluation: true,
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let remote = RequestOptions::default().with_evaluation_mode(EvaluationMode::Remote);
+
+    // When
+    let local_flags = flagsmith.get_environment_flags().unwrap();
+    let remote_flags = flagsmith
+        .with_request_options(remote)
+        .get_environment_flags()
+        .unwrap();
+
+    // Then
+    flags_mock.assert();
+    assert!(!local_flags
+        .is_feature_enabled(fixtures::FEATURE_1_NAME)
+        .unwrap());
+    assert!(remote_flags
+        .is_feature_enabled(fixtures::FEATURE_1_NAME)
+        .unwrap());
+}
+
+#[rstest]
+fn test_request_options_local_evaluation_fails_without_environment(mock_server: MockServer) {
+    // Given
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(POST).path("/api/v1/identities/");
+        then.status(200);
+    });
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+    let local = RequestOptions::default().with_evaluation_mode(EvaluationMode::Local);
+
+    // When
+    let result = flagsmith
+        .with_request_options(local)
+        .get_identity_flags_for("test_identity");
+
+    // Then
+    assert_eq!(
+        result.err().unwrap().kind,
+        flagsmith::error::ErrorKind::FlagsmithClientError
+    );
+    api_mock.assert_hits(0);
+}
+
+// Transport answering every request with the same response, recording the requests
+struct RecordingTransport {
+    response_body: serde_json::Value,
+    requests: std::sync::Mutex<Vec<HttpRequest>>,
+}
+
+impl HttpTransport for RecordingTransport {
+    fn send(&self, request: HttpRequest) -> Result<HttpResponse, flagsmith::error::Error> {
+        self.requests.lock().unwrap().push(request);
+        Ok(HttpResponse {
+            status: 200,
+            body: self.response_body.to_string().into_bytes(),
+        })
+    }
+}
+
+#[rstest]
+fn test_requests_are_sent_through_custom_transport(flags_json: serde_json::Value) {
+    // Given
+    let transport = std::sync::Arc::new(RecordingTransport {
+        response_body: flags_json,
+        requests: Default::default(),
+    });
+    let mut custom_headers = http::HeaderMap::new();
+    custom_headers.insert("X-Custom-Header", HeaderValue::from_static("custom"));
+    let flagsmith_options = FlagsmithOptions {
+        custom_headers,
+        request_timeout_seconds: 3,
+        transport: Some(transport.clone()),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    let flags = flagsmith.get_environment_flags().unwrap();
+
+    // Then
+    assert!(flags.is_feature_enabled(fixtures::FEATURE_1_NAME).unwrap());
+    let requests = transport.requests.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].method, http::Method::GET);
+    assert_eq!(
+        requests[0].url,
+        "https://edge.api.flagsmith.com/api/v1/flags/"
+    );
+    assert_eq!(requests[0].headers["X-Environment-Key"], ENVIRONMENT_KEY);
+    assert_eq!(requests[0].headers["X-Custom-Header"], "custom");
+    assert_eq!(requests[0].timeout, std::time::Duration::from_secs(3));
+}
+
+#[rstest]
+fn test_requests_go_through_proxy_with_credentials(
+    mock_server: MockServer,
+    flags_json: serde_json::Value,
+) {
+    // Given
+    let proxy_mock = mock_server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/flags/")
+            .header("X-Environment-Key", ENVIRONMENT_KEY)
+            // base64 of `user:secret`
+            .header("Proxy-Authorization", "Basic dXNlcjpzZWNyZXQ=");
+        then.status(200).json_body(flags_json);
+    });
+    let flagsmith_options = FlagsmithOptions {
+        api_url: "http://flagsmith.invalid/api/v1/".to_string(),
+        proxy: Some(ProxyOptions {
+            url: mock_server.base_url(),
+            username: Some("user".to_string()),
+            password: Some("secret".to_string()),
+        }),
+        connect_timeout_seconds: Some(1),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    let flags = flagsmith.get_environment_flags().unwrap();
+
+    // Then
+    proxy_mock.assert();
+    assert!(flags.is_feature_enabled(fixtures::FEATURE_1_NAME).unwrap());
+}
+
+#[rstest]
+fn test_proxy_options_debug_redacts_password() {
+    // Given
+    let proxy = ProxyOptions {
+        url: "http://proxy.internal:3128".to_string(),
+        username: Some("user".to_string()),
+        password: Some("secret".to_string()),
+    };
+
+    // When
+    let debug = format!("{:?}", proxy);
+
+    // Then
+    assert!(debug.contains("user"));
+    assert!(!debug.contains("secret"));
+}
+
+#[rstest]
+fn test_flagsmith_parses_root_and_client_certificates(
+    mock_server: MockServer,
+    flags_json: serde_json::Value,
+) {
+    // Given - the mock server is plain HTTP, so this only checks that the
+    // certificates are parsed and the client still sends requests, not the TLS handshake
+    let api_mock = mock_server.mock(|when, then| {
+        when.method(GET).path("/api/v1/flags/");
+        then.status(200).json_body(flags_json);
+    });
+    let flagsmith_options = FlagsmithOptions {
+        api_url: mock_server.url("/api/v1/"),
+        root_certificates: vec![std::fs::read("tests/fixtures/tls/ca.pem").unwrap()],
+        client_certificate: Some(ClientCertificate {
+            certificate_pem: std::fs::read("tests/fixtures/tls/client.pem").unwrap(),
+            private_key_pem: std::fs::read("tests/fixtures/tls/client.key").unwrap(),
+        }),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // When
+    let flags = flagsmith.get_environment_flags().unwrap();
+
+    // Then
+    api_mock.assert();
+    assert!(flags.is_feature_enabled(fixtures::FEATURE_1_NAME).unwrap());
+}
+
+#[rstest]
+#[should_panic(expected = "No certificate found in root certificate PEM.")]
+fn test_flagsmith_panics_if_root_certificate_is_invalid() {
+    let flagsmith_options = FlagsmithOptions {
+        root_certificates: vec![b"not a certificate".to_vec()],
+        ..Default::default()
+    };
+    Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+}
+
+#[rstest]
+#[should_panic(expected = "cannot be used with transport")]
+fn test_flagsmith_panics_if_both_transport_and_proxy_are_set(flags_json: serde_json::Value) {
+    let flagsmith_options = FlagsmithOptions {
+        transport: Some(std::sync::Arc::new(RecordingTransport {
+            response_body: flags_json,
+            requests: Default::default(),
+        })),
+        proxy: Some(ProxyOptions {
+            url: "http://proxy.internal:3128".to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+}
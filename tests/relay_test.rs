@@ -0,0 +1,223 @@
+use flagsmith::flagsmith::offline_handler::LocalFileHandler;
+use flagsmith::flagsmith::relay::Relay;
+use flagsmith::{Flagsmith, FlagsmithOptions};
+use flagsmith_flag_engine::environments::Environment;
+
+use http::header::{HeaderMap, HeaderValue};
+use rstest::*;
+
+mod fixtures;
+
+use fixtures::{environment_json, ENVIRONMENT_KEY};
+
+#[fixture]
+fn relay() -> Relay {
+    let handler = LocalFileHandler::new("tests/fixtures/environment.json").unwrap();
+    let flagsmith_options = FlagsmithOptions {
+        offline_handler: Some(Box::new(handler)),
+        offline_mode: true,
+        ..Default::default()
+    };
+    Relay::new(Flagsmith::new(
+        ENVIRONMENT_KEY.to_string(),
+        flagsmith_options,
+    ))
+}
+
+fn environment_key_headers(environment_key: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "X-Environment-Key",
+        HeaderValue::from_str(environment_key).unwrap(),
+    );
+    headers
+}
+
+#[rstest]
+fn test_relay_serves_environment_flags(relay: Relay) {
+    // When
+    let response = relay.handle("GET", "/api/v1/flags/", &HeaderMap::new(), b"");
+
+    // Then
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        response.body,
+        serde_json::json!([{
+            "feature": {"id": fixtures::FEATURE_1_ID, "name": fixtures::FEATURE_1_NAME},
+            "enabled": true,
+            "feature_state_value": fixtures::FEATURE_1_STR_VALUE,
+        }])
+    );
+}
+
+#[rstest]
+fn test_relay_serves_identity_flags(relay: Relay) {
+    // Given
+    let body = serde_json::json!({
+        "identifier": "some_identity",
+        "traits": [{"trait_key": "foo", "trait_value": "bar"}]
+    });
+
+    // When
+    let response = relay.handle(
+        "POST",
+        "/api/v1/identities/",
+        &HeaderMap::new(),
+        body.to_string().as_bytes(),
+    );
+
+    // Then
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body["identifier"], "some_identity");
+    assert_eq!(response.body["traits"][0]["trait_key"], "foo");
+    let flag = &response.body["flags"][0];
+    assert_eq!(flag["feature"]["name"], fixtures::FEATURE_1_NAME);
+    assert_eq!(flag["enabled"], true);
+    assert_eq!(flag["feature_state_value"], fixtures::FEATURE_1_STR_VALUE);
+}
+
+#[rstest]
+fn test_relay_serves_identity_flags_from_query_string(relay: Relay) {
+    // When
+    let response = relay.handle(
+        "GET",
+        "/api/v1/identities/?identifier=some_identity",
+        &HeaderMap::new(),
+        b"",
+    );
+
+    // Then
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body["identifier"], "some_identity");
+    assert_eq!(response.body["flags"][0]["enabled"], true);
+}
+
+#[rstest]
+fn test_relay_serves_environment_document(relay: Relay, environment_json: serde_json::Value) {
+    // Given
+    let headers = environment_key_headers(ENVIRONMENT_KEY);
+
+    // When
+    let response = relay.handle("GET", "/api/v1/environment-document/", &headers, b"");
+
+    // Then
+    assert_eq!(response.status, 200);
+    assert_eq!(response.body, environment_json);
+    let environment: Environment = serde_json::from_value(response.body).unwrap();
+    assert_eq!(environment.identity_overrides.len(), 1);
+}
+
+#[rstest]
+#[case(None)]
+#[case(Some("ser.other_environment_key"))]
+#[case(Some("client_side_key"))]
+fn test_relay_rejects_environment_document_requests_without_server_key(
+    relay: Relay,
+    #[case] environment_key: Option<&str>,
+) {
+    // Given
+    let headers = environment_key.map_or_else(HeaderMap::new, environment_key_headers);
+
+    // When
+    let response = relay.handle("GET", "/api/v1/environment-document/", &headers, b"");
+
+    // Then
+    assert_eq!(response.status, 401);
+    assert!(response.body.get("api_key").is_none());
+}
+
+#[rstest]
+fn test_relay_serves_environment_document_with_document_key(relay: Relay) {
+    // Given
+    let relay = relay.with_document_key("relay_key");
+
+    // When
+    let response = relay.handle(
+        "GET",
+        "/api/v1/environment-document/",
+        &environment_key_headers("relay_key"),
+        b"",
+    );
+    let rejected_response = relay.handle(
+        "GET",
+        "/api/v1/environment-document/",
+        &environment_key_headers(ENVIRONMENT_KEY),
+        b"",
+    );
+
+    // Then
+    assert_eq!(response.status, 200);
+    assert_eq!(rejected_response.status, 401);
+}
+
+#[rstest]
+#[case("GET", "/api/v1/unknown/", b"".as_slice(), 404)]
+#[case("DELETE", "/api/v1/flags/", b"".as_slice(), 405)]
+#[case("GET", "/api/v1/identities/", b"".as_slice(), 400)]
+#[case("POST", "/api/v1/identities/", b"{\"traits\": []}".as_slice(), 400)]
+fn test_relay_rejects_invalid_requests(
+    relay: Relay,
+    #[case] method: &str,
+    #[case] url: &str,
+    #[case] body: &[u8],
+    #[case] expected_status: u16,
+) {
+    // When
+    let response = relay.handle(method, url, &HeaderMap::new(), body);
+
+    // Then
+    assert_eq!(response.status, expected_status);
+    assert!(response.body["detail"].is_string());
+}
+
+#[rstest]
+fn test_relay_can_be_used_as_api_url(relay: Relay) {
+    // Given
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    std::thread::spawn(move || relay.serve(&address.to_string()));
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    let flagsmith_options = FlagsmithOptions {
+        api_url: format!("http://{}/api/v1/", address),
+        ..Default::default()
+    };
+    let flagsmith = Flagsmith::new("client_side_key".to_string(), flagsmith_options);
+
+    // When
+    let flags = flagsmith
+        .get_identity_flags("some_identity", None, None)
+        .unwrap();
+
+    // Then
+    let flag = flags.get_flag(fixtures::FEATURE_1_NAME).unwrap();
+    assert!(flag.enabled);
+    assert_eq!(
+        flag.value_as_string().unwrap(),
+        fixtures::FEATURE_1_STR_VALUE
+    );
+}
+
+#[rstest]
+fn test_relay_serves_environment_document_to_local_evaluation_client(relay: Relay) {
+    // Given
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    std::thread::spawn(move || relay.serve(&address.to_string()));
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    let flagsmith_options = FlagsmithOptions {
+        api_url: format!("http://{}/api/v1/", address),
+        enable_local_evaluation: true,
+        ..Default::default()
+    };
+
+    // When
+    let flagsmith = Flagsmith::new(ENVIRONMENT_KEY.to_string(), flagsmith_options);
+
+    // Then
+    let environment = flagsmith.get_environment_document().unwrap();
+    assert_eq!(environment.api_key, "B62qaMZNwfiqT76p38ggrQ");
+}